@@ -1,5 +1,7 @@
 use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
-use grider::{Grid, GridConfig};
+use grider::{
+    profile::ProjectionProfiles, Binarizer, Grid, GridConfig, GridDetector, PyramidConfig,
+};
 use image::{DynamicImage, GrayImage, Luma};
 use std::hint::black_box;
 
@@ -41,6 +43,15 @@ fn create_test_image(width: u32, height: u32, pattern: &str) -> DynamicImage {
                 Luma([255])
             }
         }),
+        "layout" => GrayImage::from_fn(width, height, |x, y| {
+            // Dense blocks of 300x200 pixels separated by 60 pixel gutters
+            let in_block = x % 360 >= 60 && y % 260 >= 60;
+            if in_block && (x.is_multiple_of(3) || y.is_multiple_of(3)) {
+                Luma([0])
+            } else {
+                Luma([255])
+            }
+        }),
         _ => GrayImage::from_pixel(width, height, Luma([255])), // Default to all white
     };
     DynamicImage::ImageLuma8(img)
@@ -125,7 +136,7 @@ fn bench_configs(c: &mut Criterion) {
 // Benchmark parallel vs sequential processing
 fn bench_parallel_processing(c: &mut Criterion) {
     let mut group = c.benchmark_group("parallel_vs_sequential");
-    let sizes = [(500, 500), (1000, 1000), (2000, 2000)];
+    let sizes = [(500, 500), (1000, 1000), (2000, 2000), (4000, 4000)];
 
     for size in sizes.iter() {
         let (width, height) = *size;
@@ -195,10 +206,111 @@ fn bench_threshold_blocks(c: &mut Criterion) {
     group.finish();
 }
 
+// Counts the ink of a row one pixel at a time, as detection used to
+fn row_ink(img: &GrayImage, y: u32, width: u32) -> u32 {
+    (0..width)
+        .filter(|&x| img.get_pixel(x, y)[0] != 255)
+        .count() as u32
+}
+
+// Counts the ink of a column one pixel at a time, as detection used to
+fn column_ink(img: &GrayImage, x: u32, height: u32) -> u32 {
+    (0..height)
+        .filter(|&y| img.get_pixel(x, y)[0] != 255)
+        .count() as u32
+}
+
+// Benchmark per-line ink counting against the single row-major pass
+fn bench_ink_counting(c: &mut Criterion) {
+    let mut group = c.benchmark_group("ink_counting");
+    let (width, height) = (4000, 4000);
+
+    for pattern in ["dense", "sparse"] {
+        let img = create_test_image(width, height, pattern).to_luma8();
+
+        group.bench_with_input(BenchmarkId::new("per_line", pattern), &img, |b, img| {
+            b.iter(|| {
+                let rows: Vec<u32> = (0..height).map(|y| row_ink(img, y, width)).collect();
+                let columns: Vec<u32> = (0..width).map(|x| column_ink(img, x, height)).collect();
+                black_box((rows, columns));
+            });
+        });
+
+        group.bench_with_input(BenchmarkId::new("single_pass", pattern), &img, |b, img| {
+            b.iter(|| {
+                black_box(ProjectionProfiles::from_binarized(img));
+            });
+        });
+    }
+    group.finish();
+}
+
+// Benchmark fresh detections against a reused detector
+fn bench_detector_reuse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("detector_reuse");
+    let img = create_test_image(1000, 1000, "dense");
+    let config = GridConfig::default();
+
+    group.bench_with_input(BenchmarkId::new("fresh", "1000x1000"), &img, |b, img| {
+        b.iter(|| {
+            black_box(Grid::try_from_image_with_config(img, config.clone()).unwrap());
+        });
+    });
+
+    let mut detector = GridDetector::new(config.clone());
+    group.bench_with_input(BenchmarkId::new("reused", "1000x1000"), &img, |b, img| {
+        b.iter(|| {
+            black_box(detector.detect(img).unwrap());
+        });
+    });
+    group.finish();
+}
+
+// Benchmark full-resolution against coarse-to-fine detection on 8K screenshots,
+// with a local and a global binarizer
+fn bench_pyramid(c: &mut Criterion) {
+    let mut group = c.benchmark_group("pyramid");
+    let img = create_test_image(7680, 4320, "layout");
+    let configs = [
+        ("full_resolution", None),
+        ("scale_4", Some(PyramidConfig::default())),
+        (
+            "scale_8",
+            Some(PyramidConfig {
+                scale: 8,
+                ..Default::default()
+            }),
+        ),
+    ];
+
+    let binarizers = [
+        ("adaptive", GridConfig::default().binarizer),
+        ("global", Binarizer::Global(128)),
+    ];
+
+    for (binarizer_name, binarizer) in binarizers {
+        for (name, pyramid) in configs {
+            let config = GridConfig {
+                binarizer,
+                pyramid,
+                ..GridConfig::default()
+            };
+            let id = format!("7680x4320_{binarizer_name}");
+            group.bench_with_input(BenchmarkId::new(id, name), &img, |b, img| {
+                b.iter(|| {
+                    black_box(Grid::try_from_image_with_config(img, config.clone()).unwrap());
+                });
+            });
+        }
+    }
+    group.finish();
+}
+
 criterion_group! {
     name = benches;
     config = Criterion::default().sample_size(20); // Reduced sample size for faster runs
     targets = bench_image_sizes, bench_patterns, bench_configs,
-              bench_parallel_processing, bench_threshold_blocks
+              bench_parallel_processing, bench_threshold_blocks, bench_ink_counting,
+              bench_detector_reuse, bench_pyramid
 }
 criterion_main!(benches);
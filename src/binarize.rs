@@ -0,0 +1,592 @@
+use image::{
+    DynamicImage, GenericImageView, GrayImage, ImageBuffer, Luma, Pixel, Primitive, Rgb, Rgba,
+    RgbaImage,
+};
+use imageproc::contrast::{threshold_mut, ThresholdType};
+use tracing::*;
+
+/// Intensity below which an estimated background is considered dark.
+const DARK_BACKGROUND_LEVEL: u8 = 128;
+
+/// How the dominant background intensity of an image is estimated.
+///
+/// When the estimated background is dark, the grayscale image is inverted before
+/// binarization so that the background is treated as empty. This makes dark-themed
+/// screenshots and white-on-black documents segment like light ones.
+///
+/// # Example
+/// ```
+/// use grider::BackgroundDetection;
+/// use image::{GrayImage, Luma};
+///
+/// let mut img = GrayImage::from_pixel(10, 10, Luma([20u8]));
+/// img.put_pixel(5, 5, Luma([240u8]));
+/// assert_eq!(BackgroundDetection::Border.estimate(&img), Some(20));
+/// assert_eq!(BackgroundDetection::Histogram.estimate(&img), Some(20));
+/// assert_eq!(BackgroundDetection::None.estimate(&img), None);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum BackgroundDetection {
+    /// Assume a light background (default).
+    #[default]
+    None,
+    /// Use the median intensity of the pixels on the image border.
+    Border,
+    /// Use the most frequent intensity of the whole image.
+    Histogram,
+}
+
+impl BackgroundDetection {
+    /// Estimates the background intensity of a grayscale image.
+    ///
+    /// # Returns
+    /// The estimated intensity, or `None` if detection is disabled or the image is empty.
+    pub fn estimate(&self, image: &GrayImage) -> Option<u8> {
+        let (width, height) = image.dimensions();
+        if width == 0 || height == 0 {
+            return None;
+        }
+        let mut counts = [0; 256];
+        self.count(image, 0, height, &mut counts);
+        self.level(&counts)
+    }
+
+    /// Returns `true` if the estimated background of the image is dark.
+    pub fn is_dark(&self, image: &GrayImage) -> bool {
+        self.estimate(image)
+            .is_some_and(|level| level < DARK_BACKGROUND_LEVEL)
+    }
+
+    /// Returns `true` if the level estimated from `counts` is dark.
+    pub(crate) fn is_dark_level(&self, counts: &Histogram) -> bool {
+        self.level(counts)
+            .is_some_and(|level| level < DARK_BACKGROUND_LEVEL)
+    }
+
+    /// Adds the intensities the estimate looks at to `counts`, for a band of
+    /// rows starting at row `y` of an image `height` rows high.
+    pub(crate) fn count(&self, band: &GrayImage, y: u32, height: u32, counts: &mut Histogram) {
+        let width = band.width() as usize;
+        match self {
+            BackgroundDetection::None => {}
+            BackgroundDetection::Border => {
+                for (row, pixels) in (y..).zip(band.as_raw().chunks_exact(width)) {
+                    if row == 0 || row == height - 1 {
+                        add_levels(pixels, counts);
+                    } else {
+                        add_levels(&[pixels[0]], counts);
+                        if width > 1 {
+                            add_levels(&[pixels[width - 1]], counts);
+                        }
+                    }
+                }
+            }
+            BackgroundDetection::Histogram => {
+                add_levels(&band.as_raw()[..width * band.height() as usize], counts)
+            }
+        }
+    }
+
+    /// Estimates the background intensity from the counts of [`Self::count`].
+    pub(crate) fn level(&self, counts: &Histogram) -> Option<u8> {
+        let total: u64 = counts.iter().sum();
+        if total == 0 {
+            return None;
+        }
+        match self {
+            BackgroundDetection::None => None,
+            BackgroundDetection::Border => {
+                let mut seen = 0;
+                counts.iter().position(|&count| {
+                    seen += count;
+                    seen * 2 >= total
+                })
+            }
+            BackgroundDetection::Histogram => (0..256usize).max_by_key(|&level| counts[level]),
+        }
+        .map(|level| level as u8)
+    }
+}
+
+/// A solid background color, with a per-channel tolerance, that marks empty pixels.
+///
+/// Legacy sprite sheets and design exports often use a key color such as magenta
+/// as their background.
+///
+/// # Example
+/// ```
+/// use grider::ColorKey;
+/// use image::Rgb;
+///
+/// let key = ColorKey::new(Rgb([255, 0, 255]), 8);
+/// assert!(key.matches(Rgb([250, 4, 255])));
+/// assert!(!key.matches(Rgb([200, 0, 255])));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorKey {
+    /// The key color.
+    pub color: Rgb<u8>,
+    /// Maximum absolute difference allowed on each channel.
+    pub tolerance: u8,
+}
+
+impl ColorKey {
+    /// Creates a new `ColorKey` with the given color and per-channel tolerance.
+    pub fn new(color: Rgb<u8>, tolerance: u8) -> Self {
+        Self { color, tolerance }
+    }
+
+    /// Returns `true` if the pixel matches the key color within tolerance.
+    pub fn matches(&self, pixel: Rgb<u8>) -> bool {
+        pixel
+            .0
+            .iter()
+            .zip(self.color.0.iter())
+            .all(|(&a, &b)| a.abs_diff(b) <= self.tolerance)
+    }
+}
+
+/// Strategy used to binarize the grayscale image before line detection.
+///
+/// Every strategy produces an image where empty (background) pixels are white (255)
+/// and ink pixels are black (0). Local strategies use
+/// [`GridConfig::threshold_block_size`](crate::GridConfig::threshold_block_size)
+/// as the radius of their window.
+///
+/// # Example
+/// ```
+/// use grider::{Binarizer, Grid, GridConfig};
+/// use image::open;
+///
+/// let img = open("tests/large.png").unwrap();
+/// let config = GridConfig {
+///     binarizer: Binarizer::Otsu,
+///     ..Default::default()
+/// };
+/// let grid = Grid::try_from_image_with_config(&img, config).unwrap();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Binarizer {
+    /// Compares each pixel with the mean of its block (default).
+    #[default]
+    Adaptive,
+    /// Fixed global threshold: pixels brighter than the level are empty.
+    Global(u8),
+    /// Global threshold chosen with Otsu's method, suited to flat-color screenshots.
+    Otsu,
+    /// Sauvola local threshold `mean * (1 + k * (std / r - 1))`, suited to
+    /// low-contrast scans. Typical values are `k = 0.2` and `r = 128.0`.
+    Sauvola { k: f32, r: f32 },
+    /// Niblack local threshold `mean + k * std`. A typical value is `k = -0.2`.
+    Niblack { k: f32 },
+}
+
+impl Binarizer {
+    /// Binarizes a grayscale image.
+    ///
+    /// # Arguments
+    /// * `image` - The grayscale image to binarize.
+    /// * `block_radius` - The window radius used by local strategies.
+    ///
+    /// # Returns
+    /// An image where empty pixels are 255 and ink pixels are 0.
+    ///
+    /// # Example
+    /// ```
+    /// use grider::Binarizer;
+    /// use image::{GrayImage, Luma};
+    ///
+    /// let img = GrayImage::from_fn(4, 1, |x, _| Luma([if x < 2 { 20 } else { 230 }]));
+    /// let binarized = Binarizer::Global(128).binarize(&img, 1);
+    /// assert_eq!(binarized.as_raw(), &vec![0, 0, 255, 255]);
+    /// ```
+    pub fn binarize(&self, image: &GrayImage, block_radius: u32) -> GrayImage {
+        let mut out = GrayImage::default();
+        self.binarize_into(image, block_radius, &mut out, &mut Integrals::default());
+        out
+    }
+
+    /// Binarizes a grayscale image into `out`, reusing its allocation and the
+    /// integral images of `integrals`.
+    pub(crate) fn binarize_into(
+        &self,
+        image: &GrayImage,
+        block_radius: u32,
+        out: &mut GrayImage,
+        integrals: &mut Integrals,
+    ) {
+        debug!("Binarizing image with {:?}", self);
+        let (width, height) = image.dimensions();
+        reuse_buffer(out, width, height);
+        match *self {
+            Binarizer::Adaptive => {
+                integrals.fill(image, false);
+                local_threshold(image, block_radius, integrals, out, |pixel, sum, _, n| {
+                    pixel as u64 >= sum / n
+                })
+            }
+            Binarizer::Global(level) => {
+                out.copy_from_slice(image);
+                threshold_mut(out, level, ThresholdType::Binary);
+            }
+            Binarizer::Otsu => {
+                let mut counts = [0; 256];
+                add_levels(image, &mut counts);
+                out.copy_from_slice(image);
+                threshold_mut(out, otsu_level(&counts), ThresholdType::Binary);
+            }
+            Binarizer::Sauvola { k, r } => {
+                let (k, r) = (k as f64, r as f64);
+                integrals.fill(image, true);
+                local_threshold(
+                    image,
+                    block_radius,
+                    integrals,
+                    out,
+                    |pixel, sum, sum_sq, n| {
+                        let (mean, std) = mean_and_std(sum, sum_sq, n);
+                        pixel as f64 >= mean * (1.0 + k * (std / r - 1.0))
+                    },
+                )
+            }
+            Binarizer::Niblack { k } => {
+                let k = k as f64;
+                integrals.fill(image, true);
+                local_threshold(
+                    image,
+                    block_radius,
+                    integrals,
+                    out,
+                    |pixel, sum, sum_sq, n| {
+                        let (mean, std) = mean_and_std(sum, sum_sq, n);
+                        pixel as f64 >= mean + k * std
+                    },
+                )
+            }
+        }
+    }
+}
+
+/// Number of pixels of each 8-bit intensity.
+pub(crate) type Histogram = [u64; 256];
+
+/// Adds the intensities of `pixels` to `counts`.
+pub(crate) fn add_levels(pixels: &[u8], counts: &mut Histogram) {
+    for &pixel in pixels {
+        counts[pixel as usize] += 1;
+    }
+}
+
+/// Returns the Otsu threshold level of a histogram: the level maximizing the
+/// variance between the background and foreground classes.
+pub(crate) fn otsu_level(counts: &Histogram) -> u8 {
+    let total_weight: u64 = counts.iter().sum();
+    let total_sum = counts
+        .iter()
+        .enumerate()
+        .fold(0f64, |sum, (level, &count)| {
+            sum + (level as u64 * count) as f64
+        });
+
+    let mut background_sum = 0f64;
+    let mut background_weight = 0;
+    let mut largest_variance = 0f64;
+    let mut best_level = 0;
+
+    for (level, &count) in counts.iter().enumerate() {
+        background_weight += count;
+        if background_weight == 0 {
+            continue;
+        }
+        let foreground_weight = total_weight - background_weight;
+        if foreground_weight == 0 {
+            break;
+        }
+
+        background_sum += (level as u64 * count) as f64;
+        let background_mean = background_sum / background_weight as f64;
+        let foreground_mean = (total_sum - background_sum) / foreground_weight as f64;
+
+        let variance = background_weight as f64
+            * foreground_weight as f64
+            * (background_mean - foreground_mean).powi(2);
+        if variance > largest_variance {
+            largest_variance = variance;
+            best_level = level as u8;
+        }
+    }
+    best_level
+}
+
+/// Integral images of the pixel values and of their squares, kept between calls
+/// so that their allocations are reused.
+#[derive(Debug, Default)]
+pub(crate) struct Integrals {
+    /// Row stride of both tables: the image width plus one.
+    stride: usize,
+    sum: Vec<u64>,
+    sum_squared: Vec<u64>,
+}
+
+impl Integrals {
+    /// Fills the tables for an image, with a leading row and column of zeros.
+    fn fill(&mut self, image: &GrayImage, squared: bool) {
+        let (width, height) = image.dimensions();
+        self.stride = width as usize + 1;
+        let len = self.stride * (height as usize + 1);
+        self.sum.clear();
+        self.sum.resize(len, 0);
+        self.sum_squared.clear();
+        if squared {
+            self.sum_squared.resize(len, 0);
+        }
+
+        for (y, pixels) in image.rows().enumerate() {
+            let (mut row_sum, mut row_sum_squared) = (0, 0);
+            for (x, pixel) in pixels.enumerate() {
+                let value = pixel[0] as u64;
+                let (above, here) = (y * self.stride + x + 1, (y + 1) * self.stride + x + 1);
+                row_sum += value;
+                self.sum[here] = self.sum[above] + row_sum;
+                if squared {
+                    row_sum_squared += value * value;
+                    self.sum_squared[here] = self.sum_squared[above] + row_sum_squared;
+                }
+            }
+        }
+    }
+
+    /// Sums a table over the inclusive block between two corners.
+    fn block(
+        &self,
+        table: &[u64],
+        (x_low, y_low): (u32, u32),
+        (x_high, y_high): (u32, u32),
+    ) -> u64 {
+        let (x_low, y_low) = (x_low as usize, y_low as usize);
+        let (x_high, y_high) = (x_high as usize + 1, y_high as usize + 1);
+        table[y_high * self.stride + x_high] + table[y_low * self.stride + x_low]
+            - table[y_low * self.stride + x_high]
+            - table[y_high * self.stride + x_low]
+    }
+}
+
+/// Computes the mean and standard deviation of a block from its sums.
+fn mean_and_std(sum: u64, sum_squared: u64, n: u64) -> (f64, f64) {
+    let (sum, sum_squared, n) = (sum as f64, sum_squared as f64, n as f64);
+    let mean = sum / n;
+    (mean, (sum_squared / n - mean * mean).max(0.0).sqrt())
+}
+
+/// Applies a local threshold over the (2 * `block_radius` + 1) square block
+/// centered on each pixel. `is_empty` receives the pixel value, the sum and
+/// squared sum of the block and its number of pixels.
+fn local_threshold(
+    image: &GrayImage,
+    block_radius: u32,
+    integrals: &Integrals,
+    out: &mut GrayImage,
+    is_empty: impl Fn(u8, u64, u64, u64) -> bool,
+) {
+    let (width, height) = image.dimensions();
+    for (x, y, pixel) in out.enumerate_pixels_mut() {
+        let low = (
+            x.saturating_sub(block_radius),
+            y.saturating_sub(block_radius),
+        );
+        let high = (
+            (x + block_radius).min(width - 1),
+            (y + block_radius).min(height - 1),
+        );
+
+        let n = ((high.0 - low.0 + 1) * (high.1 - low.1 + 1)) as u64;
+        let sum = integrals.block(&integrals.sum, low, high);
+        let sum_squared = if integrals.sum_squared.is_empty() {
+            0
+        } else {
+            integrals.block(&integrals.sum_squared, low, high)
+        };
+
+        *pixel = if is_empty(image.get_pixel(x, y)[0], sum, sum_squared, n) {
+            Luma([255])
+        } else {
+            Luma([0])
+        };
+    }
+}
+
+/// Resizes a buffer to the given dimensions, reusing its allocation.
+pub(crate) fn reuse_buffer(buffer: &mut GrayImage, width: u32, height: u32) {
+    if buffer.dimensions() != (width, height) {
+        let mut raw = std::mem::take(buffer).into_raw();
+        raw.resize(width as usize * height as usize, 0);
+        *buffer = GrayImage::from_raw(width, height, raw).expect("buffer matches dimensions");
+    }
+}
+
+/// Calls generic code on the image buffer wrapped by a `DynamicImage`, so that
+/// it reads the native pixels without converting the whole image first.
+macro_rules! with_buffer {
+    ($image:expr, $buffer:ident => $body:expr) => {
+        match $image {
+            image::DynamicImage::ImageLuma8($buffer) => $body,
+            image::DynamicImage::ImageLumaA8($buffer) => $body,
+            image::DynamicImage::ImageRgb8($buffer) => $body,
+            image::DynamicImage::ImageRgba8($buffer) => $body,
+            image::DynamicImage::ImageLuma16($buffer) => $body,
+            image::DynamicImage::ImageLumaA16($buffer) => $body,
+            image::DynamicImage::ImageRgb16($buffer) => $body,
+            image::DynamicImage::ImageRgba16($buffer) => $body,
+            image::DynamicImage::ImageRgb32F($buffer) => $body,
+            image::DynamicImage::ImageRgba32F($buffer) => $body,
+            other => {
+                let $buffer = &other.to_rgba32f();
+                $body
+            }
+        }
+    };
+}
+pub(crate) use with_buffer;
+
+/// Converts a subpixel to 8 bits, scaled by the maximum value of its type.
+fn to_u8<S: Primitive>(value: S) -> u8 {
+    let max = S::DEFAULT_MAX_VALUE.to_f32().unwrap_or(1.0);
+    let value = value.to_f32().unwrap_or(0.0) / max;
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+/// Number of rows of a view converted to grayscale at once.
+const LUMA_STRIP_ROWS: u32 = 64;
+
+/// Writes the 8-bit grayscale version of an image into `out`, converted by
+/// [`DynamicImage::to_luma8`] so that the gray levels match those of the image
+/// crate.
+///
+/// Images are converted strip by strip, in their own pixel type, so that the
+/// buffer of `out` is reused.
+pub(crate) fn luma_into(image: &DynamicImage, out: &mut GrayImage) {
+    use DynamicImage::*;
+    match image {
+        ImageLuma8(img) => {
+            reuse_buffer(out, img.width(), img.height());
+            out.copy_from_slice(img);
+        }
+        ImageLumaA8(img) => luma_buffer_into(img, out, ImageLumaA8, DynamicImage::into_luma_alpha8),
+        ImageRgb8(img) => luma_buffer_into(img, out, ImageRgb8, DynamicImage::into_rgb8),
+        ImageRgba8(img) => luma_buffer_into(img, out, ImageRgba8, DynamicImage::into_rgba8),
+        ImageLuma16(img) => luma_buffer_into(img, out, ImageLuma16, DynamicImage::into_luma16),
+        ImageLumaA16(img) => {
+            luma_buffer_into(img, out, ImageLumaA16, DynamicImage::into_luma_alpha16)
+        }
+        ImageRgb16(img) => luma_buffer_into(img, out, ImageRgb16, DynamicImage::into_rgb16),
+        ImageRgba16(img) => luma_buffer_into(img, out, ImageRgba16, DynamicImage::into_rgba16),
+        ImageRgb32F(img) => luma_buffer_into(img, out, ImageRgb32F, DynamicImage::into_rgb32f),
+        ImageRgba32F(img) => luma_buffer_into(img, out, ImageRgba32F, DynamicImage::into_rgba32f),
+        other => luma_view_into(other, out),
+    }
+}
+
+/// Converts an image buffer to grayscale into `out`, copying the rows of each
+/// strip into a buffer of the same pixel type.
+fn luma_buffer_into<P: Pixel>(
+    image: &ImageBuffer<P, Vec<P::Subpixel>>,
+    out: &mut GrayImage,
+    wrap: fn(ImageBuffer<P, Vec<P::Subpixel>>) -> DynamicImage,
+    unwrap: fn(DynamicImage) -> ImageBuffer<P, Vec<P::Subpixel>>,
+) {
+    let row_len = image.width() as usize * P::CHANNEL_COUNT as usize;
+    luma_strips_into(
+        image.dimensions(),
+        out,
+        |strip, top| {
+            let rows = &image.as_raw()[top as usize * row_len..];
+            let len = strip.len();
+            strip.copy_from_slice(&rows[..len]);
+        },
+        wrap,
+        unwrap,
+    );
+}
+
+/// Writes the 8-bit grayscale version of any image view into `out`.
+///
+/// Strips of rows are copied as 8-bit RGBA and converted by
+/// [`DynamicImage::to_luma8`], so that 8-bit views get the gray levels of the
+/// same pixels in a [`DynamicImage`].
+pub(crate) fn luma_view_into<I: GenericImageView>(image: &I, out: &mut GrayImage) {
+    luma_strips_into(
+        image.dimensions(),
+        out,
+        |strip: &mut RgbaImage, top| {
+            for (x, y, pixel) in strip.enumerate_pixels_mut() {
+                let Rgba(channels) = image.get_pixel(x, top + y).to_rgba();
+                *pixel = Rgba(channels.map(to_u8));
+            }
+        },
+        DynamicImage::ImageRgba8,
+        DynamicImage::into_rgba8,
+    );
+}
+
+/// Converts an image of `dimensions` to grayscale into `out`, one strip of rows
+/// at a time.
+///
+/// `fill` copies the rows starting at a row into the strip, which `wrap` turns
+/// into a [`DynamicImage`] converted by [`DynamicImage::to_luma8`]; `unwrap`
+/// recovers the strip for the next rows.
+fn luma_strips_into<P: Pixel>(
+    (width, height): (u32, u32),
+    out: &mut GrayImage,
+    mut fill: impl FnMut(&mut ImageBuffer<P, Vec<P::Subpixel>>, u32),
+    wrap: fn(ImageBuffer<P, Vec<P::Subpixel>>) -> DynamicImage,
+    unwrap: fn(DynamicImage) -> ImageBuffer<P, Vec<P::Subpixel>>,
+) {
+    reuse_buffer(out, width, height);
+    let mut strip = ImageBuffer::default();
+    for top in (0..height).step_by(LUMA_STRIP_ROWS as usize) {
+        let rows = LUMA_STRIP_ROWS.min(height - top);
+        if strip.dimensions() != (width, rows) {
+            strip = ImageBuffer::new(width, rows);
+        }
+        fill(&mut strip, top);
+
+        let image = wrap(strip);
+        let offset = top as usize * width as usize;
+        let luma = image.to_luma8();
+        out.as_mut()[offset..offset + luma.len()].copy_from_slice(&luma);
+        strip = unwrap(image);
+    }
+}
+
+/// Binarizes the alpha channel into `out`: pixels whose alpha is below `cutoff`
+/// are empty (255) and all other pixels are ink (0).
+pub(crate) fn alpha_mask_into<I: GenericImageView>(image: &I, cutoff: u8, out: &mut GrayImage) {
+    let (width, height) = image.dimensions();
+    reuse_buffer(out, width, height);
+    for (x, y, pixel) in out.enumerate_pixels_mut() {
+        *pixel = if to_u8(image.get_pixel(x, y).alpha()) < cutoff {
+            Luma([255])
+        } else {
+            Luma([0])
+        };
+    }
+}
+
+/// Binarizes by a key color into `out`: pixels matching the key are empty (255)
+/// and all other pixels are ink (0).
+pub(crate) fn color_key_mask_into<I: GenericImageView>(
+    image: &I,
+    key: &ColorKey,
+    out: &mut GrayImage,
+) {
+    let (width, height) = image.dimensions();
+    reuse_buffer(out, width, height);
+    for (x, y, pixel) in out.enumerate_pixels_mut() {
+        let Rgb(channels) = image.get_pixel(x, y).to_rgb();
+        *pixel = if key.matches(Rgb(channels.map(to_u8))) {
+            Luma([255])
+        } else {
+            Luma([0])
+        };
+    }
+}
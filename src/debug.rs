@@ -28,6 +28,7 @@ use super::*;
 /// let drawing_config = GridDrawingConfig::default();
 /// debug::save_image_with_grid(&img, &grid, "output_with_grid.png", &drawing_config).unwrap();
 /// ```
+#[cfg_attr(not(feature = "drawing"), allow(unused_mut, unused_variables))]
 pub fn save_image_with_grid(
     image: &DynamicImage,
     grid: &Grid,
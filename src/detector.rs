@@ -0,0 +1,337 @@
+use crate::binarize::{self, reuse_buffer, Integrals};
+use crate::profile::ProjectionProfiles;
+use crate::{BackgroundDetection, Binarizer, Grid, GridConfig, GridError};
+use image::{imageops, ColorType, DynamicImage, GenericImageView, GrayImage, Pixel};
+use std::ops::Range;
+use tracing::*;
+
+/// A reusable grid detector for batches of images.
+///
+/// The detector owns a [`GridConfig`] together with the grayscale, binarized and
+/// integral-image buffers and the projection profiles used during detection.
+/// They are kept between calls to [`GridDetector::detect`], so processing many
+/// images of the same size allocates them only once.
+///
+/// # Example
+/// ```
+/// use grider::{GridConfig, GridDetector};
+/// use image::open;
+///
+/// let img = open("tests/large.png").unwrap();
+/// let mut detector = GridDetector::new(GridConfig::default());
+///
+/// for _ in 0..3 {
+///     let grid = detector.detect(&img).unwrap();
+///     assert!(!grid.rows.is_empty());
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub struct GridDetector {
+    config: GridConfig,
+    gray: GrayImage,
+    binarized: GrayImage,
+    cleaned: GrayImage,
+    integrals: Integrals,
+    profiles: ProjectionProfiles,
+}
+
+impl GridDetector {
+    /// Creates a detector with the given configuration and empty buffers.
+    pub fn new(config: GridConfig) -> Self {
+        Self {
+            config,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the configuration of the detector.
+    pub fn config(&self) -> &GridConfig {
+        &self.config
+    }
+
+    /// Detects the grid of an image, reusing the buffers of previous calls.
+    ///
+    /// The result is the same as [`Grid::try_from_image_with_config`].
+    pub fn detect(&mut self, image: &DynamicImage) -> Result<Grid, GridError> {
+        if let Some(pyramid) = self.config.pyramid.filter(|pyramid| pyramid.scale > 1) {
+            validate_dimensions(image.dimensions())?;
+            return self.detect_coarse_to_fine(image, pyramid);
+        }
+        let _span = debug_span!("detect_grid").entered();
+        self.binarize(image)?;
+        self.detect_binarized()
+    }
+
+    /// Detects the grid of any image view, reusing the buffers of previous calls.
+    ///
+    /// The result is the same as [`Grid::try_from_view_with_config`]; views are
+    /// always detected at full resolution, ignoring [`GridConfig::pyramid`].
+    pub fn detect_view<I: GenericImageView>(&mut self, image: &I) -> Result<Grid, GridError> {
+        let _span = debug_span!("detect_grid").entered();
+        self.binarize_view(image)?;
+        self.apply_mask((0, 0))?;
+        self.detect_binarized()
+    }
+
+    /// Returns the last binarized image, where white pixels (255) are empty and
+    /// any other value is ink.
+    pub(crate) fn binarized(&self) -> &GrayImage {
+        &self.binarized
+    }
+
+    /// Returns `true` if images of this color type are binarized by luma
+    /// thresholding rather than by a color key or alpha mask.
+    pub(crate) fn uses_luma(&self, color: ColorType) -> bool {
+        let config = &self.config;
+        config.color_key.is_none() && !(config.alpha_cutoff.is_some() && color.has_alpha())
+    }
+
+    /// Returns the number of lines local binarizers need on each side of a
+    /// region to binarize it as part of the whole image.
+    ///
+    /// Configurations needing statistics of the whole image are rejected with
+    /// [`GridError::UnsupportedConfig`].
+    pub(crate) fn local_overlap(&self, color: ColorType) -> Result<u32, GridError> {
+        let config = &self.config;
+        if config.separators.is_some() {
+            return Err(unsupported("separators need the whole image"));
+        }
+        if !self.uses_luma(color) {
+            return Ok(0);
+        }
+        if config.background != BackgroundDetection::None {
+            return Err(unsupported("background detection needs the whole image"));
+        }
+        match config.binarizer {
+            Binarizer::Otsu => Err(unsupported("Otsu binarization needs the whole image")),
+            Binarizer::Global(_) => Ok(0),
+            _ => Ok(config.threshold_block_size),
+        }
+    }
+
+    /// Counts the ink of `rows` across the whole width of the image, binarizing
+    /// them with `overlap` rows on each side.
+    pub(crate) fn rows_ink(
+        &mut self,
+        image: &DynamicImage,
+        rows: Range<u32>,
+        overlap: u32,
+    ) -> Result<Vec<u32>, GridError> {
+        let (width, height) = image.dimensions();
+        let band = expand(&rows, overlap, height);
+        let rows_band = image.crop_imm(0, band.start, width, band.len() as u32);
+        self.binarize_at(&rows_band, (0, band.start))?;
+        let pixels = self.binarized.as_raw();
+        Ok(rows
+            .map(|y| {
+                let offset = (y - band.start) as usize * width as usize;
+                let row = &pixels[offset..offset + width as usize];
+                row.iter().filter(|&&pixel| pixel != 255).count() as u32
+            })
+            .collect())
+    }
+
+    /// Counts the ink of `columns` across the whole height of the image,
+    /// binarizing them with `overlap` columns on each side.
+    pub(crate) fn columns_ink(
+        &mut self,
+        image: &DynamicImage,
+        columns: Range<u32>,
+        overlap: u32,
+    ) -> Result<Vec<u32>, GridError> {
+        let (width, height) = image.dimensions();
+        let band = expand(&columns, overlap, width);
+        let columns_band = image.crop_imm(band.start, 0, band.len() as u32, height);
+        self.binarize_at(&columns_band, (band.start, 0))?;
+        // Count row by row, so that columns do not stride across memory
+        let mut ink = vec![0; columns.len()];
+        let offset = (columns.start - band.start) as usize;
+        for row in self.binarized.as_raw().chunks_exact(band.len()) {
+            for (ink, &pixel) in ink.iter_mut().zip(&row[offset..]) {
+                *ink += (pixel != 255) as u32;
+            }
+        }
+        Ok(ink)
+    }
+
+    /// Detects rows and columns on the binarized buffer.
+    fn detect_binarized(&mut self) -> Result<Grid, GridError> {
+        Grid::from_binarized_with_scratch(
+            &self.binarized,
+            &self.config,
+            &mut self.profiles,
+            &mut self.cleaned,
+        )
+    }
+
+    /// Validates the image and binarizes it into the binarized buffer.
+    pub(crate) fn binarize(&mut self, image: &DynamicImage) -> Result<(), GridError> {
+        self.binarize_at(image, (0, 0))
+    }
+
+    /// Validates part of an image whose top-left pixel is at `origin` in the
+    /// image, binarizes it into the binarized buffer and clears the pixels
+    /// masked by [`GridConfig::mask`].
+    pub(crate) fn binarize_at(
+        &mut self,
+        image: &DynamicImage,
+        origin: (u32, u32),
+    ) -> Result<(), GridError> {
+        self.binarize_pixels(image)?;
+        self.apply_mask(origin)
+    }
+
+    /// Clears the masked pixels of the binarized buffer, whose top-left pixel
+    /// is at `origin` in the image.
+    fn apply_mask(&mut self, origin: (u32, u32)) -> Result<(), GridError> {
+        match &self.config.mask {
+            Some(mask) => mask.apply(&mut self.binarized, origin),
+            None => Ok(()),
+        }
+    }
+
+    /// Binarizes an image into the binarized buffer, ignoring the mask.
+    fn binarize_pixels(&mut self, image: &DynamicImage) -> Result<(), GridError> {
+        trace!("Binarizing image with config: {:?}", self.config);
+        validate_dimensions(image.dimensions())?;
+
+        // Use the key color as the emptiness signal
+        if let Some(key) = &self.config.color_key {
+            let _span = debug_span!("color_key_mask").entered();
+            debug!("Binarizing image from color key {:?}", key);
+            binarize::with_buffer!(image, buffer => {
+                binarize::color_key_mask_into(buffer, key, &mut self.binarized)
+            });
+            return Ok(());
+        }
+
+        // Use transparency as the emptiness signal for images that carry it
+        if let Some(cutoff) = self.config.alpha_cutoff {
+            if image.color().has_alpha() {
+                let _span = debug_span!("alpha_mask").entered();
+                debug!("Binarizing image from alpha channel with cutoff={}", cutoff);
+                binarize::with_buffer!(image, buffer => {
+                    binarize::alpha_mask_into(buffer, cutoff, &mut self.binarized)
+                });
+                return Ok(());
+            }
+            debug!("Image has no alpha channel, falling back to luma thresholding");
+        }
+
+        // Convert to grayscale, borrowing images that already are
+        match image {
+            DynamicImage::ImageLuma8(img) => self.binarize_luma(Some(img)),
+            other => {
+                debug_span!("grayscale").in_scope(|| binarize::luma_into(other, &mut self.gray));
+                self.binarize_luma(None)
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the grayscale version of an image, borrowing images that already
+    /// are and converting the others into the grayscale buffer.
+    pub(crate) fn luma<'a>(&'a mut self, image: &'a DynamicImage) -> &'a GrayImage {
+        match image {
+            DynamicImage::ImageLuma8(img) => img,
+            other => {
+                debug_span!("grayscale").in_scope(|| binarize::luma_into(other, &mut self.gray));
+                &self.gray
+            }
+        }
+    }
+
+    /// Validates any image view and binarizes it into the binarized buffer,
+    /// ignoring the mask.
+    pub(crate) fn binarize_view<I: GenericImageView>(
+        &mut self,
+        image: &I,
+    ) -> Result<(), GridError> {
+        trace!("Binarizing image view with config: {:?}", self.config);
+        validate_dimensions(image.dimensions())?;
+
+        // Use the key color as the emptiness signal
+        if let Some(key) = &self.config.color_key {
+            let _span = debug_span!("color_key_mask").entered();
+            debug!("Binarizing image from color key {:?}", key);
+            binarize::color_key_mask_into(image, key, &mut self.binarized);
+            return Ok(());
+        }
+
+        // Use transparency as the emptiness signal for images that carry it
+        if let Some(cutoff) = self.config.alpha_cutoff {
+            if <I::Pixel as Pixel>::HAS_ALPHA {
+                let _span = debug_span!("alpha_mask").entered();
+                debug!("Binarizing image from alpha channel with cutoff={}", cutoff);
+                binarize::alpha_mask_into(image, cutoff, &mut self.binarized);
+                return Ok(());
+            }
+            debug!("Image has no alpha channel, falling back to luma thresholding");
+        }
+
+        // Convert to grayscale
+        debug_span!("grayscale").in_scope(|| binarize::luma_view_into(image, &mut self.gray));
+        self.binarize_luma(None);
+        Ok(())
+    }
+
+    /// Binarizes a grayscale image, or the grayscale buffer if `borrowed` is
+    /// `None`, inverting it first when its background is dark.
+    ///
+    /// A borrowed image is only copied when it has to be inverted.
+    fn binarize_luma(&mut self, mut borrowed: Option<&GrayImage>) {
+        let config = &self.config;
+
+        // Make the background light so that it binarizes as empty
+        let img = borrowed.unwrap_or(&self.gray);
+        if config.invert != config.background.is_dark(img) {
+            debug!("Inverting image before binarization");
+            if let Some(img) = borrowed.take() {
+                reuse_buffer(&mut self.gray, img.width(), img.height());
+                self.gray.copy_from_slice(img);
+            }
+            imageops::invert(&mut self.gray);
+        }
+
+        // Apply the configured binarization strategy
+        let img = borrowed.unwrap_or(&self.gray);
+        debug_span!("threshold").in_scope(|| {
+            config.binarizer.binarize_into(
+                img,
+                config.threshold_block_size,
+                &mut self.binarized,
+                &mut self.integrals,
+            )
+        });
+    }
+}
+
+/// Rejects images without pixels.
+fn validate_dimensions((width, height): (u32, u32)) -> Result<(), GridError> {
+    if width == 0 || height == 0 {
+        error!(
+            "Invalid image dimensions: width={}, height={}",
+            width, height
+        );
+        return Err(GridError::InvalidDimensions { width, height });
+    }
+    Ok(())
+}
+
+/// Extends a range by `overlap` lines on each side, within `0..len`.
+pub(crate) fn expand(range: &Range<u32>, overlap: u32, len: u32) -> Range<u32> {
+    range.start.saturating_sub(overlap)..len.min(range.end.saturating_add(overlap))
+}
+
+/// Clips a span starting at `start` to `0..len`.
+pub(crate) fn clip(start: i32, size: u32, len: u32) -> Range<u32> {
+    let start = start as i64;
+    let end = (start + size as i64).clamp(0, len as i64) as u32;
+    start.clamp(0, end as i64) as u32..end
+}
+
+/// Builds the error of a configuration that cannot be applied to part of an image.
+fn unsupported(reason: &str) -> GridError {
+    error!("Unsupported configuration: {}", reason);
+    GridError::UnsupportedConfig(reason.to_string())
+}
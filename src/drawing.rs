@@ -1,9 +1,12 @@
-use crate::{Cell, Grid, GridError};
+use crate::{Cell, Grid, GridError, LineKind};
 use image::*;
 use imageproc::drawing::*;
 use imageproc::rect::Rect;
 use std::fmt;
 
+/// Function providing a color for a line based on its kind.
+pub type KindColorProvider = Box<dyn Fn(&LineKind) -> Rgba<u8>>;
+
 /// Configuration for drawing grids, cells, and grid lines.
 ///
 /// This struct allows customization of padding, colors, and line thickness.
@@ -23,6 +26,7 @@ use std::fmt;
 ///     cell_background_color: Rgba([200, 200, 200, 255]), // Light gray for cells
 ///     row_color_provider: None, // Use uniform row color
 ///     column_color_provider: None, // Use uniform column color
+///     kind_color_provider: None, // Do not color lines by kind
 ///     line_thickness: 1,
 /// };
 /// ```
@@ -39,6 +43,9 @@ pub struct GridDrawingConfig {
     pub row_color_provider: Option<Box<dyn Fn(usize) -> Rgba<u8>>>,
     /// Optional function to provide custom colors for columns based on their index.
     pub column_color_provider: Option<Box<dyn Fn(usize) -> Rgba<u8>>>,
+    /// Optional function to provide custom colors for rows and columns based on their kind.
+    /// Index-based providers take precedence; see [`kind_color`] for a ready-made palette.
+    pub kind_color_provider: Option<KindColorProvider>,
     /// Thickness of grid lines.
     pub line_thickness: u32,
 }
@@ -53,6 +60,7 @@ impl fmt::Debug for GridDrawingConfig {
             .field("cell_background_color", &self.cell_background_color)
             .field("row_color_provider", &"<function>")
             .field("column_color_provider", &"<function>")
+            .field("kind_color_provider", &"<function>")
             .field("line_thickness", &self.line_thickness)
             .finish()
     }
@@ -67,11 +75,33 @@ impl Default for GridDrawingConfig {
             cell_background_color: Rgba([200, 200, 200, 255]), // Light gray
             row_color_provider: None,
             column_color_provider: None,
+            kind_color_provider: None,
             line_thickness: 2,
         }
     }
 }
 
+/// Default palette telling gutters, rules and content apart.
+///
+/// # Examples
+///
+/// ```
+/// use grider::{drawing::*, LineKind};
+///
+/// let config = GridDrawingConfig {
+///     kind_color_provider: Some(Box::new(kind_color)),
+///     ..Default::default()
+/// };
+/// ```
+pub fn kind_color(kind: &LineKind) -> Rgba<u8> {
+    match kind {
+        LineKind::Empty => Rgba([160, 160, 160, 255]), // Gray
+        LineKind::Full => Rgba([0, 160, 0, 255]),      // Green
+        LineKind::Sparse => Rgba([230, 160, 0, 255]),  // Orange
+        LineKind::Separator => Rgba([160, 0, 160, 255]), // Purple
+    }
+}
+
 /// Trait for types that can be drawn on an image.
 ///
 /// This trait is implemented for [`Cell`], [`Grid`], and other types that represent
@@ -136,6 +166,8 @@ impl Drawable for Grid {
             let y = row.y + row.height;
             let color = if let Some(ref provider) = config.row_color_provider {
                 provider(row_index)
+            } else if let Some(ref provider) = config.kind_color_provider {
+                provider(&row.kind)
             } else {
                 config.row_color
             };
@@ -152,6 +184,8 @@ impl Drawable for Grid {
             let x = column.x + column.width;
             let color = if let Some(ref provider) = config.column_color_provider {
                 provider(col_index)
+            } else if let Some(ref provider) = config.kind_color_provider {
+                provider(&column.kind)
             } else {
                 config.column_color
             };
@@ -0,0 +1,274 @@
+use crate::detector::{clip, expand};
+use crate::profile::{ProfileValue, ProjectionProfiles};
+use crate::{
+    Column, Grid, GridConfig, GridDetector, GridError, LineInfo, LineTrait, MergeDirection, Row,
+    SmallVecLine,
+};
+use image::{DynamicImage, GenericImageView};
+use imageproc::rect::Rect;
+use std::ops::Range;
+use tracing::*;
+
+impl GridDetector {
+    /// Updates the grid of a frame in which only the `dirty` rectangle changed
+    /// since the frame of `previous`.
+    ///
+    /// Local binarizers let the rectangle change the binarization of the pixels
+    /// around it, so the rows and columns affected are those crossing the
+    /// rectangle widened by [`GridConfig::threshold_block_size`]. Only they are
+    /// binarized again, along with the lines the binarizer needs around them,
+    /// and their ink replaces theirs in `profiles`. If the kind of one of these
+    /// lines changed, only the previous lines around them are segmented again;
+    /// otherwise the previous lines are kept as they are. The result is the same
+    /// as detecting the whole frame.
+    ///
+    /// # Arguments
+    /// * `image` - The new frame.
+    /// * `previous` - The grid of the previous frame.
+    /// * `profiles` - The ink profiles of the previous frame, updated in place.
+    /// * `dirty` - The region of the frame that changed, clipped to the frame.
+    ///
+    /// # Errors
+    /// [`GridError::InvalidDimensions`] if the profiles do not match the frame,
+    /// and [`GridError::UnsupportedConfig`] if the configuration needs
+    /// statistics of the whole image, such as [`crate::Binarizer::Otsu`].
+    ///
+    /// # Example
+    /// ```
+    /// use grider::{Grid, GridConfig, GridDetector};
+    /// use image::{open, Rgba};
+    /// use imageproc::{drawing::draw_filled_rect_mut, rect::Rect};
+    ///
+    /// let img = open("tests/large.png").unwrap();
+    /// let mut detector = GridDetector::new(GridConfig::default());
+    /// let mut profiles =
+    ///     Grid::try_profiles_from_image_with_config(&img, GridConfig::default()).unwrap();
+    /// let grid = detector.detect(&img).unwrap();
+    ///
+    /// // A notification pops up in a corner of the next frame
+    /// let dirty = Rect::at(10, 10).of_size(40, 20);
+    /// let mut frame = img.clone();
+    /// draw_filled_rect_mut(&mut frame, dirty, Rgba([0, 0, 0, 255]));
+    ///
+    /// let updated = detector.update(&frame, &grid, &mut profiles, dirty).unwrap();
+    /// assert_eq!(updated, detector.detect(&frame).unwrap());
+    /// ```
+    pub fn update(
+        &mut self,
+        image: &DynamicImage,
+        previous: &Grid,
+        profiles: &mut ProjectionProfiles,
+        dirty: Rect,
+    ) -> Result<Grid, GridError> {
+        let _span = debug_span!("update_grid").entered();
+        let (width, height) = image.dimensions();
+        if (profiles.width(), profiles.height()) != (width, height) {
+            error!(
+                "Profiles of {}x{} do not match the image of {}x{}",
+                profiles.width(),
+                profiles.height(),
+                width,
+                height
+            );
+            return Err(GridError::InvalidDimensions { width, height });
+        }
+        let overlap = self.local_overlap(image.color())?;
+        let rows = clip(dirty.top(), dirty.height(), height);
+        let columns = clip(dirty.left(), dirty.width(), width);
+        if rows.is_empty() || columns.is_empty() {
+            return Ok(previous.clone());
+        }
+        let (rows, columns) = (
+            expand(&rows, overlap, height),
+            expand(&columns, overlap, width),
+        );
+        debug!(
+            "Updating rows {:?} and columns {:?} with an overlap of {}",
+            rows, columns, overlap
+        );
+
+        // Recount the ink of the affected lines
+        let rows_ink = self.rows_ink(image, rows.clone(), overlap)?;
+        let columns_ink = self.columns_ink(image, columns.clone(), overlap)?;
+
+        let config = self.config();
+        let (row_ink, column_ink) = (&mut profiles.rows, &mut profiles.columns);
+        let previous_rows: Vec<LineInfo> = previous
+            .rows
+            .iter()
+            .map(|row| LineInfo::new(row.y, row.height, row.kind.clone()))
+            .collect();
+        let previous_columns: Vec<LineInfo> = previous
+            .columns
+            .iter()
+            .map(|column| LineInfo::new(column.x, column.width, column.kind.clone()))
+            .collect();
+        let rows = debug_span!("row_pass")
+            .in_scope(|| update_lines(row_ink, rows, rows_ink, width, config, &previous_rows));
+        let columns = debug_span!("column_pass").in_scope(|| {
+            update_lines(
+                column_ink,
+                columns,
+                columns_ink,
+                height,
+                config,
+                &previous_columns,
+            )
+        });
+        let (rows, columns) = (
+            rows.into_iter().map(Row::new).collect(),
+            columns.into_iter().map(Column::new).collect(),
+        );
+        Ok(Grid { rows, columns })
+    }
+}
+
+impl Grid {
+    /// Updates the grid of a frame in which only the `dirty` rectangle changed,
+    /// from the grid and ink profiles of the previous frame.
+    ///
+    /// See [`GridDetector::update`] for details; detectors reuse their buffers
+    /// from one frame to the next.
+    pub fn try_update_from_image_with_config(
+        &self,
+        image: &DynamicImage,
+        profiles: &mut ProjectionProfiles,
+        dirty: Rect,
+        config: GridConfig,
+    ) -> Result<Self, GridError> {
+        trace!("Updating grid with config: {:?}", config);
+        GridDetector::new(config).update(image, self, profiles, dirty)
+    }
+}
+
+/// Replaces the ink of the lines of `range` in a profile and segments the
+/// lines around them again.
+///
+/// If the kind of none of the lines changed, the `previous` lines are kept as
+/// they are. Otherwise only a window of them is segmented again and spliced
+/// between the previous lines before and after it:
+/// - The window starts one line before the last line ending before the
+///   content reaching `range`. The run starting the window, the short runs it
+///   absorbs (hysteresis and minimum run lengths, which measure content up to
+///   the next gap) and its merge into the line before are unchanged.
+/// - It ends at the first previous line boundary after `range` that the new
+///   lines of the window share, with the previous line after that boundary
+///   and the content reaching its end still inside the window, so that the
+///   runs and merges after it are unchanged. The window is widened until it
+///   holds such a boundary.
+///
+/// Statistical merge thresholds (see [`crate::LineStatistic`]) depend on every
+/// run: they are computed from the runs of the whole profile, and the whole
+/// profile is merged again if the lengths of the runs of the window changed.
+/// Merging into the smaller neighbor picks the smallest line of the whole
+/// profile at each step, so it always segments the whole profile again.
+fn update_lines(
+    profile: &mut [u32],
+    range: Range<u32>,
+    mut ink: Vec<u32>,
+    scale: u32,
+    config: &GridConfig,
+    previous: &[LineInfo],
+) -> SmallVecLine<LineInfo> {
+    let classify = |value: u32| value.classify(scale, config);
+    let lines = &mut profile[range.start as usize..range.end as usize];
+    let changed = lines
+        .iter()
+        .zip(&ink)
+        .any(|(&old, &new)| classify(old) != classify(new));
+    // Keep the previous ink of the lines to compare their runs
+    lines.swap_with_slice(&mut ink);
+    let (profile, previous_ink) = (&*profile, ink);
+
+    if !changed {
+        trace!(
+            "Line kinds of {:?} unchanged, keeping previous lines",
+            range
+        );
+        return previous.iter().cloned().collect();
+    }
+    if config.merge_direction != MergeDirection::Previous {
+        return Grid::segment_profile(profile, scale, config);
+    }
+
+    let runs = |window: Range<u32>, ink: &dyn Fn(u32) -> u32| {
+        Grid::constrain_runs(Grid::collect_runs_in(window, |i| classify(ink(i))), config)
+    };
+    let new_ink = |i: u32| profile[i as usize];
+    let old_ink = |i: u32| match i.checked_sub(range.start) {
+        Some(offset) if i < range.end => previous_ink[offset as usize],
+        _ => profile[i as usize],
+    };
+    let all_runs =
+        (!config.merge_threshold.is_fixed()).then(|| runs(0..profile.len() as u32, &new_ink));
+    let thresholds = config.merge_threshold.compute(
+        all_runs.as_deref().unwrap_or_default(),
+        config.merge_threshold_ratio,
+    );
+
+    let end = |line: &LineInfo| line.start + line.length;
+    let is_content = |i: u32| classify(profile[i as usize]).has_content();
+    // The window starts one line before the last line ending before the
+    // content reaching `range`, whose lines are unchanged
+    let content_start = (0..range.start)
+        .rev()
+        .find(|&i| !is_content(i))
+        .map_or(0, |gap| gap + 1);
+    let first = previous
+        .iter()
+        .rposition(|line| end(line) < content_start)
+        .map_or(0, |last| last.saturating_sub(1));
+    let after = previous
+        .iter()
+        .position(|line| end(line) >= range.end)
+        .unwrap_or(previous.len() - 1);
+    let mut margin = 1;
+    loop {
+        let last = (after + margin).min(previous.len() - 1);
+        // Content is measured up to the next gap, which the window must hold
+        let content_end = (end(&previous[last])..profile.len() as u32)
+            .find(|&i| !is_content(i))
+            .unwrap_or(profile.len() as u32);
+        let window = previous[first].start..content_end;
+        let window_runs = runs(window.clone(), &new_ink);
+        // Statistical thresholds are unchanged if the lengths of the runs are
+        let lengths = |runs: &[LineInfo]| {
+            let mut lengths: Vec<_> = runs.iter().map(|r| (r.kind.index(), r.length)).collect();
+            lengths.sort_unstable();
+            lengths
+        };
+        if let Some(all_runs) = all_runs
+            .as_ref()
+            .filter(|_| lengths(&window_runs) != lengths(&runs(window.clone(), &old_ink)))
+        {
+            trace!("Runs of {:?} changed, merging the whole profile", window);
+            return Grid::merge_small_lines_with_thresholds(all_runs.clone(), thresholds, config);
+        }
+        let lines = Grid::merge_small_lines_with_thresholds(window_runs, thresholds, config);
+
+        // A boundary shared with the previous lines, with a previous line after it
+        let anchor = if last == previous.len() - 1 {
+            Some((previous.len(), lines.len()))
+        } else {
+            (after..last).find_map(|i| {
+                let boundary = end(&previous[i]);
+                let shared = lines.binary_search_by_key(&boundary, end).ok()?;
+                Some((i + 1, shared + 1))
+            })
+        };
+        if let Some((next, count)) = anchor {
+            trace!(
+                "Segmented {:?} again, keeping {} previous lines",
+                window,
+                first + previous.len() - next
+            );
+            return previous[..first]
+                .iter()
+                .cloned()
+                .chain(lines.into_iter().take(count))
+                .chain(previous[next..].iter().cloned())
+                .collect();
+        }
+        margin *= 2;
+    }
+}
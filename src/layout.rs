@@ -0,0 +1,122 @@
+use crate::{Grid, GridConfig, GridDetector, GridError};
+use image::{imageops, DynamicImage, GrayImage};
+use imageproc::rect::Rect;
+use tracing::*;
+
+/// A node of the hierarchical layout tree built by
+/// [`Grid::try_tree_from_image_with_config`].
+///
+/// Every node covers a rectangular region of the source image and holds the
+/// grid detected inside it. All coordinates, including those of the nested
+/// grids, are absolute image coordinates.
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct GridNode {
+    /// X-coordinate of the region.
+    pub x: u32,
+    /// Y-coordinate of the region.
+    pub y: u32,
+    /// Width of the region.
+    pub width: u32,
+    /// Height of the region.
+    pub height: u32,
+    /// Grid detected inside the region.
+    pub grid: Grid,
+    /// Layouts of the content cells of `grid`, in row-major order.
+    pub children: Vec<GridNode>,
+}
+
+impl GridNode {
+    /// Returns the depth of the tree below this node (0 for a leaf).
+    pub fn depth(&self) -> u32 {
+        self.children
+            .iter()
+            .map(|child| child.depth() + 1)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Returns `true` if the node has no children.
+    pub fn is_leaf(&self) -> bool {
+        self.children.is_empty()
+    }
+}
+
+impl From<&GridNode> for Rect {
+    fn from(node: &GridNode) -> Self {
+        Rect::at(node.x as i32, node.y as i32).of_size(node.width, node.height)
+    }
+}
+
+impl Grid {
+    /// Creates a hierarchical layout tree from an image using recursive XY-cut.
+    ///
+    /// Rows and columns are detected on the whole image first, then detection
+    /// is re-run inside every cell whose row and column both hold content
+    /// (`Full` or `Sparse`), down to [`GridConfig::max_depth`] levels. A region
+    /// that cannot be split any further becomes a leaf.
+    ///
+    /// # Example
+    /// ```
+    /// use grider::{Grid, GridConfig};
+    /// use image::open;
+    ///
+    /// let img = open("tests/large.png").unwrap();
+    /// let tree = Grid::try_tree_from_image_with_config(&img, GridConfig::default()).unwrap();
+    ///
+    /// for child in &tree.children {
+    ///     println!("Region at ({}, {}) with {} rows", child.x, child.y, child.grid.rows.len());
+    /// }
+    /// ```
+    pub fn try_tree_from_image_with_config(
+        image: &DynamicImage,
+        config: GridConfig,
+    ) -> Result<GridNode, GridError> {
+        trace!("Building layout tree with config: {:?}", config);
+        let mut detector = GridDetector::new(config);
+        detector.binarize(image)?;
+        let binarized_img = detector.binarized();
+        let (width, height) = binarized_img.dimensions();
+        Self::build_node(binarized_img, (0, 0, width, height), detector.config(), 0)
+    }
+
+    /// Detects the grid of a region and recurses into its content cells.
+    fn build_node(
+        binarized_img: &GrayImage,
+        (x, y, width, height): (u32, u32, u32, u32),
+        config: &GridConfig,
+        depth: u32,
+    ) -> Result<GridNode, GridError> {
+        debug!(
+            "Building layout node at depth={} for region x={}, y={}, width={}, height={}",
+            depth, x, y, width, height
+        );
+        let region = imageops::crop_imm(binarized_img, x, y, width, height).to_image();
+        let grid = Self::from_binarized(&region, config)?.translated(x, y);
+
+        // A region that was not split would yield the same grid again
+        let is_split = grid.rows.len() > 1 || grid.columns.len() > 1;
+        let mut children = Vec::new();
+        if is_split && depth < config.max_depth {
+            for row in grid.filtered_rows(|row| row.kind.has_content()) {
+                for column in grid.filtered_columns(|col| col.kind.has_content()) {
+                    children.push(Self::build_node(
+                        binarized_img,
+                        (column.x, row.y, column.width, row.height),
+                        config,
+                        depth + 1,
+                    )?);
+                }
+            }
+        }
+
+        Ok(GridNode {
+            x,
+            y,
+            width,
+            height,
+            grid,
+            children,
+        })
+    }
+}
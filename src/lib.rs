@@ -1,6 +1,7 @@
 //! This module provides functionality for processing images into grids of rows and columns.
 //! It uses the `image` and `imageproc` crates for image manipulation and `insta` for snapshot testing.
 
+mod binarize;
 /// Debug module for visualizing the grid on the image.
 ///
 /// # Example
@@ -16,6 +17,7 @@
 /// grider::debug::save_image_with_grid(&img, &grid, "output.png", &GridDrawingConfig::default());
 /// ```
 pub mod debug;
+mod detector;
 /// This module provides functionality for drawing grids, cells, and grid lines on images.
 /// It is feature-gated under the `drawing` feature and requires the `image` and `imageproc` crates.
 ///
@@ -44,6 +46,7 @@ pub mod debug;
 ///     cell_background_color: Rgba([200, 200, 200, 255]), // Light gray for cells
 ///     row_color_provider: None, // Use uniform row color
 ///     column_color_provider: None, // Use uniform column color
+///     kind_color_provider: None, // Do not color lines by kind
 ///     line_thickness: 1,
 /// };
 ///
@@ -51,9 +54,59 @@ pub mod debug;
 /// debug::save_image_with_grid(&img, &grid, "output_with_grid.png", &drawing_config).unwrap();
 /// ```
 pub mod drawing;
+mod incremental;
+/// Recursive XY-cut layout detection.
+///
+/// Re-runs row and column detection inside every `Full` cell of a [`Grid`],
+/// producing a tree of nested grids in absolute image coordinates.
+///
+/// # Example
+/// ```
+/// use grider::{Grid, GridConfig};
+/// use image::open;
+///
+/// let img = open("tests/large.png").unwrap();
+/// let config = GridConfig {
+///     max_depth: 2,
+///     ..Default::default()
+/// };
+/// let tree = Grid::try_tree_from_image_with_config(&img, config).unwrap();
+/// assert!(tree.depth() <= 2);
+/// ```
+pub mod layout;
+mod mask;
+/// Projection profiles: the number of ink pixels in every row and column.
+///
+/// # Example
+/// ```
+/// use grider::{Grid, GridConfig};
+/// use image::open;
+///
+/// let img = open("tests/large.png").unwrap();
+/// let profiles = Grid::try_profiles_from_image_with_config(&img, GridConfig::default()).unwrap();
+/// assert_eq!(profiles.rows.len() as u32, img.height());
+/// assert_eq!(profiles.columns.len() as u32, img.width());
+/// ```
+pub mod profile;
+mod pyramid;
+mod raw;
+mod streaming;
+mod tiled;
+pub use binarize::{BackgroundDetection, Binarizer, ColorKey};
+pub use detector::GridDetector;
 use image::*;
-use imageproc::{contrast::adaptive_threshold, rect::Rect};
+use imageproc::rect::Rect;
+pub use mask::RegionMask;
+use profile::{ProfileValue, ProjectionProfiles};
+pub use pyramid::PyramidConfig;
+pub use raw::PixelFormat;
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
 use smallvec::SmallVec;
+use std::ops::Range;
+#[cfg(feature = "rayon")]
+use std::sync::Arc;
+pub use streaming::GridStream;
 use thiserror::Error;
 use tracing::*;
 
@@ -61,6 +114,10 @@ use tracing::*;
 const DEFAULT_SMALLVEC_SIZE: usize = 32;
 const DEFAULT_THRESHOLD_BLOCK_SIZE: u32 = 12;
 const DEFAULT_MERGE_THRESHOLD_RATIO: f32 = 0.8;
+const DEFAULT_MAX_DEPTH: u32 = 3;
+const DEFAULT_PARALLEL_CHUNK_LEN: u32 = 1024;
+const DEFAULT_SEPARATOR_MIN_COVERAGE: f32 = 0.8;
+const DEFAULT_SEPARATOR_MAX_THICKNESS: u32 = 3;
 
 #[derive(Error, Debug)]
 pub enum GridError {
@@ -81,6 +138,24 @@ pub enum GridError {
 
     #[error("Column not found at x={x}")]
     ColumnNotFound { x: u32 },
+
+    #[error("Invalid stride: stride={stride}, minimum={minimum}")]
+    InvalidStride { stride: usize, minimum: usize },
+
+    #[error("Raw buffer too small: length={length}, required={required}")]
+    BufferTooSmall { length: usize, required: usize },
+
+    #[error("Invalid band: start={start}, width={width}, height={height}")]
+    InvalidBand { start: u32, width: u32, height: u32 },
+
+    #[error("Memory limit too small: limit={limit}, required={required}")]
+    MemoryLimitTooSmall { limit: usize, required: usize },
+
+    #[error("Unsupported configuration: {0}")]
+    UnsupportedConfig(String),
+
+    #[error("Invalid mask dimensions: width={width}, height={height}")]
+    InvalidMask { width: u32, height: u32 },
 }
 
 /// A type alias for SmallVec with an optimized stack-allocated buffer size.
@@ -90,21 +165,90 @@ pub type SmallVecLine<T> = SmallVec<[T; DEFAULT_SMALLVEC_SIZE]>;
 ///
 /// # Example
 /// ```
-/// use grider::GridConfig;
+/// use grider::{
+///     BackgroundDetection, Binarizer, GridConfig, InkTolerance, LineStatistic, MergeDirection,
+///     MergePolicy, MergeThreshold,
+/// };
 ///
 /// let config = GridConfig::default();
 /// assert_eq!(config.threshold_block_size, 12);
 /// assert_eq!(config.merge_threshold_ratio, 0.8);
 /// assert_eq!(config.enable_parallel, true);
+/// assert_eq!(config.ink_tolerance, InkTolerance::Pixels(0));
+/// assert_eq!(config.max_depth, 3);
+/// assert_eq!(config.binarizer, Binarizer::Adaptive);
+/// assert_eq!(config.background, BackgroundDetection::None);
+/// assert_eq!(config.invert, false);
+/// assert_eq!(config.alpha_cutoff, None);
+/// assert_eq!(config.color_key, None);
+/// assert_eq!(config.separators, None);
+/// assert_eq!(config.sparse_ratio, None);
+/// assert_eq!(config.merge_policy, MergePolicy::KeepFirst);
+/// assert_eq!(config.merge_direction, MergeDirection::Previous);
+/// assert_eq!(config.merge_threshold, MergeThreshold::Uniform(LineStatistic::Mean));
+/// assert_eq!(config.min_empty_run, 0);
+/// assert_eq!(config.min_full_run, 0);
+/// assert_eq!(config.hysteresis, 0);
+/// assert_eq!(config.parallel_chunk_len, 1024);
+/// assert_eq!(config.pyramid, None);
+/// assert_eq!(config.mask, None);
 /// ```
 #[derive(Debug, Clone)]
 pub struct GridConfig {
-    /// Block size for adaptive thresholding (default: 12)
+    /// Block radius for local thresholding (default: 12)
     pub threshold_block_size: u32,
+    /// Binarization strategy (default: [`Binarizer::Adaptive`])
+    pub binarizer: Binarizer,
+    /// Background estimation; a dark background inverts the image (default: none)
+    pub background: BackgroundDetection,
+    /// Invert the image before binarization, e.g. for white-on-black documents (default: false)
+    pub invert: bool,
+    /// Treat pixels with an alpha below this value as empty, bypassing luma
+    /// thresholding; `Some(1)` keeps only fully transparent pixels empty (default: none)
+    pub alpha_cutoff: Option<u8>,
+    /// Treat pixels matching this key color as empty, bypassing luma
+    /// thresholding; takes precedence over `alpha_cutoff` (default: none)
+    pub color_key: Option<ColorKey>,
     /// Ratio for merging small lines (default: 0.8)
     pub merge_threshold_ratio: f32,
-    /// Enable parallel processing (default: true)
+    /// Statistic of the line lengths the merge ratio applies to (default: mean of all lines)
+    pub merge_threshold: MergeThreshold,
+    /// How the kind of merged lines is decided (default: [`MergePolicy::KeepFirst`])
+    pub merge_policy: MergePolicy,
+    /// Which neighbor small lines are merged into (default: [`MergeDirection::Previous`])
+    pub merge_direction: MergeDirection,
+    /// Empty runs shorter than this are absorbed by their neighbors (default: 0)
+    pub min_empty_run: u32,
+    /// Content (`Full` or `Sparse`) runs shorter than this are absorbed by their
+    /// neighbors (default: 0)
+    pub min_full_run: u32,
+    /// Number of consecutive lines of another kind required before the kind
+    /// changes; 0 and 1 change on every line (default: 0)
+    pub hysteresis: u32,
+    /// Enable parallel processing; without the `rayon` feature, lines are
+    /// always processed sequentially (default: true)
     pub enable_parallel: bool,
+    /// Thread pool running parallel processing instead of the global rayon
+    /// pool (default: the global pool)
+    #[cfg(feature = "rayon")]
+    pub thread_pool: Option<Arc<rayon::ThreadPool>>,
+    /// Number of lines per chunk when a dimension is processed in parallel;
+    /// the result does not depend on it (default: 1024)
+    pub parallel_chunk_len: u32,
+    /// Amount of ink a row or column may contain and still be empty (default: no ink)
+    pub ink_tolerance: InkTolerance,
+    /// Detect ruled lines as separators (default: disabled)
+    pub separators: Option<SeparatorConfig>,
+    /// Classify lines whose ink ratio is below this value as sparse (default: disabled)
+    pub sparse_ratio: Option<f32>,
+    /// Maximum nesting depth for recursive layout detection (default: 3)
+    pub max_depth: u32,
+    /// Detect on a downscaled image and refine the boundaries at full
+    /// resolution, only from whole images; see [`PyramidConfig`] (default: disabled)
+    pub pyramid: Option<PyramidConfig>,
+    /// Region of interest and excluded regions, whose masked pixels are empty
+    /// (default: the whole image)
+    pub mask: Option<RegionMask>,
 }
 
 impl GridConfig {
@@ -126,8 +270,39 @@ impl GridConfig {
     ) -> Self {
         Self {
             threshold_block_size: threshold_block_size.max(3), // Minimum block size
+            binarizer: Binarizer::default(),
+            background: BackgroundDetection::default(),
+            invert: false,
+            alpha_cutoff: None,
+            color_key: None,
             merge_threshold_ratio,
+            merge_threshold: MergeThreshold::default(),
+            merge_policy: MergePolicy::default(),
+            merge_direction: MergeDirection::default(),
+            min_empty_run: 0,
+            min_full_run: 0,
+            hysteresis: 0,
             enable_parallel,
+            #[cfg(feature = "rayon")]
+            thread_pool: None,
+            parallel_chunk_len: DEFAULT_PARALLEL_CHUNK_LEN,
+            ink_tolerance: InkTolerance::default(),
+            separators: None,
+            sparse_ratio: None,
+            max_depth: DEFAULT_MAX_DEPTH,
+            pyramid: None,
+            mask: None,
+        }
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl GridConfig {
+    /// Runs `f` in the configured thread pool, or in the current pool if none is set.
+    fn install<R: Send>(&self, f: impl FnOnce() -> R + Send) -> R {
+        match &self.thread_pool {
+            Some(pool) => pool.install(f),
+            None => f(),
         }
     }
 }
@@ -142,12 +317,387 @@ impl Default for GridConfig {
     }
 }
 
+/// Maximum amount of ink (non-white binarized pixels) a line may contain
+/// while still being classified as [`LineKind::Empty`].
+///
+/// Allowing a little ink keeps JPEG specks and antialiasing pixels from
+/// splitting gutters in scanned or compressed images.
+///
+/// # Example
+/// ```
+/// use grider::InkTolerance;
+///
+/// assert_eq!(InkTolerance::Pixels(2).max_ink(100), 2);
+/// assert_eq!(InkTolerance::Ratio(0.05).max_ink(100), 5);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InkTolerance {
+    /// Maximum number of ink pixels per line.
+    Pixels(u32),
+    /// Maximum ratio of ink pixels to the line length, between 0.0 and 1.0.
+    Ratio(f32),
+}
+
+impl InkTolerance {
+    /// Returns the maximum number of ink pixels allowed in a line of the given length.
+    pub fn max_ink(&self, line_length: u32) -> u32 {
+        match *self {
+            InkTolerance::Pixels(pixels) => pixels.min(line_length),
+            InkTolerance::Ratio(ratio) => {
+                (line_length as f32 * ratio.clamp(0.0, 1.0)).floor() as u32
+            }
+        }
+    }
+}
+
+impl Default for InkTolerance {
+    fn default() -> Self {
+        InkTolerance::Pixels(0)
+    }
+}
+
+/// Statistic of the line lengths used to derive the merge threshold.
+///
+/// Except for [`LineStatistic::Absolute`], the statistic is scaled by
+/// [`GridConfig::merge_threshold_ratio`]. The median, percentiles and trimmed
+/// means keep a single huge margin from inflating the threshold.
+///
+/// # Example
+/// ```
+/// use grider::LineStatistic;
+///
+/// let lengths = [2, 4, 6, 8, 1000];
+/// assert_eq!(LineStatistic::Mean.compute(&lengths, 1.0), 204);
+/// assert_eq!(LineStatistic::Median.compute(&lengths, 1.0), 6);
+/// assert_eq!(LineStatistic::Percentile(25.0).compute(&lengths, 0.5), 2);
+/// assert_eq!(LineStatistic::TrimmedMean(0.2).compute(&lengths, 1.0), 6);
+/// assert_eq!(LineStatistic::Absolute(5).compute(&lengths, 0.5), 5);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum LineStatistic {
+    /// Integer mean of the line lengths (default).
+    #[default]
+    Mean,
+    /// Median of the line lengths.
+    Median,
+    /// Nearest-rank percentile of the line lengths, between 0.0 and 100.0.
+    Percentile(f32),
+    /// Mean after dropping this fraction of the shortest and of the longest
+    /// lines, between 0.0 and 0.5.
+    TrimmedMean(f32),
+    /// Fixed length in pixels, ignoring the lines and the ratio.
+    Absolute(u32),
+}
+
+impl LineStatistic {
+    /// Computes the merge threshold from line lengths, scaled by `ratio`.
+    ///
+    /// # Returns
+    /// The threshold in pixels, or 0 if there are no lengths (except for
+    /// [`LineStatistic::Absolute`]).
+    pub fn compute(&self, lengths: &[u32], ratio: f32) -> u32 {
+        if let LineStatistic::Absolute(pixels) = *self {
+            return pixels;
+        }
+        if lengths.is_empty() {
+            return 0;
+        }
+        let mut sorted = lengths.to_vec();
+        sorted.sort_unstable();
+        let mean = |lengths: &[u32]| {
+            let total: u64 = lengths.iter().map(|&l| l as u64).sum();
+            (total / lengths.len().max(1) as u64) as u32
+        };
+        let n = sorted.len();
+        let value = match *self {
+            LineStatistic::Mean => mean(&sorted),
+            LineStatistic::Median if n.is_multiple_of(2) => {
+                ((sorted[n / 2 - 1] as u64 + sorted[n / 2] as u64) / 2) as u32
+            }
+            LineStatistic::Median => sorted[n / 2],
+            LineStatistic::Percentile(percentile) => {
+                let rank = (percentile.clamp(0.0, 100.0) / 100.0 * (n - 1) as f32).round();
+                sorted[rank as usize]
+            }
+            LineStatistic::TrimmedMean(fraction) => {
+                let trimmed =
+                    ((n as f32 * fraction.clamp(0.0, 0.5)).floor() as usize).min((n - 1) / 2);
+                mean(&sorted[trimmed..n - trimmed])
+            }
+            LineStatistic::Absolute(pixels) => pixels,
+        };
+        (value as f32 * ratio) as u32
+    }
+}
+
+/// How the merge threshold is derived from the detected lines.
+///
+/// # Example
+/// ```
+/// use grider::{GridConfig, LineStatistic, MergeThreshold};
+///
+/// // Gutters shorter than 4 pixels are merged, content rows use the median
+/// let config = GridConfig {
+///     merge_threshold: MergeThreshold::PerKind {
+///         empty: LineStatistic::Absolute(4),
+///         content: LineStatistic::Median,
+///     },
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MergeThreshold {
+    /// One threshold for every line, computed over all lines.
+    Uniform(LineStatistic),
+    /// Separate thresholds for empty lines and for content (`Full` or `Sparse`)
+    /// lines, each computed over the lines of its own kind.
+    PerKind {
+        empty: LineStatistic,
+        content: LineStatistic,
+    },
+}
+
+impl MergeThreshold {
+    /// Computes the thresholds for empty and content lines, in that order.
+    ///
+    /// Separators are left out so that thin ruling lines do not lower the thresholds.
+    pub fn compute(&self, lines: &[LineInfo], ratio: f32) -> (u32, u32) {
+        let lengths = |filter: fn(&LineKind) -> bool| -> Vec<u32> {
+            lines
+                .iter()
+                .filter(|l| filter(&l.kind))
+                .map(|l| l.length)
+                .collect()
+        };
+        match self {
+            MergeThreshold::Uniform(statistic) => {
+                let threshold = statistic.compute(&lengths(|k| !k.is_separator()), ratio);
+                (threshold, threshold)
+            }
+            MergeThreshold::PerKind { empty, content } => (
+                empty.compute(&lengths(LineKind::is_empty), ratio),
+                content.compute(&lengths(LineKind::has_content), ratio),
+            ),
+        }
+    }
+
+    /// Returns `true` if the thresholds do not depend on the lines.
+    pub(crate) fn is_fixed(&self) -> bool {
+        matches!(
+            self,
+            MergeThreshold::Uniform(LineStatistic::Absolute(_))
+                | MergeThreshold::PerKind {
+                    empty: LineStatistic::Absolute(_),
+                    content: LineStatistic::Absolute(_),
+                }
+        )
+    }
+}
+
+impl Default for MergeThreshold {
+    fn default() -> Self {
+        MergeThreshold::Uniform(LineStatistic::default())
+    }
+}
+
+/// Configuration for detecting ruled lines, such as table borders, as separators.
+///
+/// A line is solid when its ink covers at least `min_coverage` of its length. A run
+/// of solid lines no thicker than `max_thickness` becomes a [`LineKind::Separator`],
+/// which is never merged with its neighbors and therefore bounds the cells around it.
+///
+/// # Example
+/// ```
+/// use grider::SeparatorConfig;
+///
+/// let separators = SeparatorConfig::default();
+/// assert_eq!(separators.min_coverage, 0.8);
+/// assert_eq!(separators.max_thickness, 3);
+/// assert!(separators.is_solid(90, 100));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SeparatorConfig {
+    /// Minimum ratio of ink pixels to the line length for a line to be solid (default: 0.8)
+    pub min_coverage: f32,
+    /// Maximum thickness of a run of solid lines to be a separator (default: 3)
+    pub max_thickness: u32,
+}
+
+impl SeparatorConfig {
+    /// Returns `true` if a line with `ink` ink pixels out of `length` is solid.
+    pub fn is_solid(&self, ink: u32, length: u32) -> bool {
+        ink as f32 >= self.min_coverage * length as f32
+    }
+}
+
+impl Default for SeparatorConfig {
+    fn default() -> Self {
+        SeparatorConfig {
+            min_coverage: DEFAULT_SEPARATOR_MIN_COVERAGE,
+            max_thickness: DEFAULT_SEPARATOR_MAX_THICKNESS,
+        }
+    }
+}
+
+/// How the kind of a line is decided when small lines are merged together.
+///
+/// # Example
+/// ```
+/// use grider::{GridConfig, MergeDirection, MergePolicy};
+///
+/// let config = GridConfig {
+///     merge_policy: MergePolicy::MajorityByPixels,
+///     merge_direction: MergeDirection::SmallerNeighbor,
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MergePolicy {
+    /// Keep the kind of the first merged line (default).
+    #[default]
+    KeepFirst,
+    /// Use the kind covering the most pixels.
+    MajorityByPixels,
+    /// Use `Full` if any merged line is full, then `Sparse`, otherwise the first kind.
+    AnyFullWins,
+    /// Never merge lines of different kinds.
+    SameKindOnly,
+}
+
+/// Which neighbor a line smaller than the merge threshold is merged into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MergeDirection {
+    /// Merge into the previous line (default).
+    #[default]
+    Previous,
+    /// Merge into the smaller of the two neighbors, smallest lines first.
+    SmallerNeighbor,
+}
+
+/// A run of merged lines, tracking how many pixels each kind covers.
+struct MergeGroup {
+    start: u32,
+    length: u32,
+    first_kind: LineKind,
+    pixels: [u32; LineKind::COUNT],
+}
+
+impl MergeGroup {
+    fn new(line: LineInfo) -> Self {
+        let mut pixels = [0; LineKind::COUNT];
+        pixels[line.kind.index()] = line.length;
+        MergeGroup {
+            start: line.start,
+            length: line.length,
+            first_kind: line.kind,
+            pixels,
+        }
+    }
+
+    /// Returns `true` if the groups may be merged under the policy.
+    fn can_merge(&self, other: &MergeGroup, policy: MergePolicy) -> bool {
+        let crosses_separator =
+            self.first_kind == LineKind::Separator || other.first_kind == LineKind::Separator;
+        let crosses_kinds = self.first_kind != other.first_kind;
+        !(crosses_separator || (policy == MergePolicy::SameKindOnly && crosses_kinds))
+    }
+
+    /// Absorbs an adjacent group, before or after this one.
+    fn absorb(&mut self, other: MergeGroup) {
+        if other.start < self.start {
+            self.start = other.start;
+            self.first_kind = other.first_kind;
+        }
+        self.length += other.length;
+        for (pixels, other_pixels) in self.pixels.iter_mut().zip(other.pixels) {
+            *pixels += other_pixels;
+        }
+    }
+
+    /// Resolves the kind of the group under the policy.
+    fn kind(&self, policy: MergePolicy) -> LineKind {
+        let covers = |kind: &LineKind| self.pixels[kind.index()] > 0;
+        match policy {
+            MergePolicy::KeepFirst | MergePolicy::SameKindOnly => self.first_kind.clone(),
+            MergePolicy::AnyFullWins if covers(&LineKind::Full) => LineKind::Full,
+            MergePolicy::AnyFullWins if covers(&LineKind::Sparse) => LineKind::Sparse,
+            MergePolicy::AnyFullWins => self.first_kind.clone(),
+            MergePolicy::MajorityByPixels => {
+                // Ties favor the first kind, then content over gutters
+                [
+                    self.first_kind.clone(),
+                    LineKind::Full,
+                    LineKind::Sparse,
+                    LineKind::Empty,
+                    LineKind::Separator,
+                ]
+                .into_iter()
+                .fold(None, |best: Option<LineKind>, kind| match best {
+                    Some(best) if self.pixels[best.index()] >= self.pixels[kind.index()] => {
+                        Some(best)
+                    }
+                    _ => Some(kind),
+                })
+                .unwrap_or(LineKind::Empty)
+            }
+        }
+    }
+
+    fn to_line(&self, policy: MergePolicy) -> LineInfo {
+        LineInfo::new(self.start, self.length, self.kind(policy))
+    }
+}
+
 /// Represents the kind of a line (row or column).
 #[derive(Debug, PartialEq, Clone)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum LineKind {
+    /// A line without ink, such as a gutter or margin.
     Empty,
+    /// A line holding content.
     Full,
+    /// A thin, near-solid ruling line, such as a table border.
+    Separator,
+    /// A line holding little content, below [`GridConfig::sparse_ratio`].
+    Sparse,
+}
+
+impl LineKind {
+    /// Number of line kinds.
+    const COUNT: usize = 4;
+
+    /// Returns a distinct index for each kind, below [`LineKind::COUNT`].
+    pub(crate) fn index(&self) -> usize {
+        match self {
+            LineKind::Empty => 0,
+            LineKind::Full => 1,
+            LineKind::Separator => 2,
+            LineKind::Sparse => 3,
+        }
+    }
+
+    /// Returns `true` for lines without ink.
+    pub fn is_empty(&self) -> bool {
+        *self == LineKind::Empty
+    }
+
+    /// Returns `true` for lines holding content, whether full or sparse.
+    ///
+    /// # Example
+    /// ```
+    /// use grider::LineKind;
+    ///
+    /// assert!(LineKind::Sparse.has_content());
+    /// assert!(!LineKind::Separator.has_content());
+    /// ```
+    pub fn has_content(&self) -> bool {
+        matches!(self, LineKind::Full | LineKind::Sparse)
+    }
+
+    /// Returns `true` for ruling lines.
+    pub fn is_separator(&self) -> bool {
+        *self == LineKind::Separator
+    }
 }
 
 /// Information about a line in the grid.
@@ -461,32 +1011,139 @@ impl Grid {
         config: GridConfig,
     ) -> Result<Self, GridError> {
         trace!("Processing image with config: {:?}", config);
-        // Validate image dimensions
-        let (width, height) = image.dimensions();
-        if width == 0 || height == 0 {
-            error!(
-                "Invalid image dimensions: width={}, height={}",
-                width, height
-            );
-            return Err(GridError::InvalidDimensions { width, height });
-        }
+        GridDetector::new(config).detect(image)
+    }
 
-        // Convert to grayscale
-        let img = image.to_luma8();
+    /// Creates a grid from any image view with custom configuration.
+    ///
+    /// Unlike [`Grid::try_from_image_with_config`], the image does not have to be
+    /// wrapped in a [`DynamicImage`]: any [`GenericImageView`], such as a `Luma16`,
+    /// `Rgb32F` or `Rgba16` buffer or a sub-image view, is read pixel by pixel
+    /// without converting it first.
+    ///
+    /// # Example
+    /// ```
+    /// use grider::{Grid, GridConfig};
+    /// use image::{ImageBuffer, Luma};
+    ///
+    /// let img: ImageBuffer<Luma<u16>, Vec<u16>> =
+    ///     ImageBuffer::from_fn(20, 20, |_, y| Luma([if y < 10 { 0 } else { u16::MAX }]));
+    /// let config = GridConfig {
+    ///     binarizer: grider::Binarizer::Global(128),
+    ///     ..Default::default()
+    /// };
+    /// let grid = Grid::try_from_view_with_config(&img, config).unwrap();
+    /// assert_eq!(grid.rows.len(), 2);
+    /// ```
+    pub fn try_from_view_with_config<I: GenericImageView>(
+        image: &I,
+        config: GridConfig,
+    ) -> Result<Self, GridError> {
+        trace!("Processing image view with config: {:?}", config);
+        GridDetector::new(config).detect_view(image)
+    }
 
-        // Apply adaptive thresholding with configured block size
-        let binarized_img = adaptive_threshold(&img, config.threshold_block_size);
+    /// Detects rows and columns on an already binarized image.
+    pub(crate) fn from_binarized(
+        binarized_img: &GrayImage,
+        config: &GridConfig,
+    ) -> Result<Self, GridError> {
+        Self::from_binarized_with_scratch(
+            binarized_img,
+            config,
+            &mut ProjectionProfiles::default(),
+            &mut GrayImage::default(),
+        )
+    }
 
-        // Process rows and columns based on configuration
-        let (rows, columns) = if config.enable_parallel {
-            Self::process_lines_parallel(&binarized_img, config.merge_threshold_ratio)?
-        } else {
-            Self::process_lines_sequential(&binarized_img, config.merge_threshold_ratio)?
+    /// Detects rows and columns on an already binarized image, reusing the
+    /// allocations of `profiles` and of `cleaned_img`, which receives the image
+    /// without its separators.
+    pub(crate) fn from_binarized_with_scratch(
+        binarized_img: &GrayImage,
+        config: &GridConfig,
+        profiles: &mut ProjectionProfiles,
+        cleaned_img: &mut GrayImage,
+    ) -> Result<Self, GridError> {
+        let (width, height) = binarized_img.dimensions();
+
+        // Count the ink of every row and column in one pass
+        let fill_profiles = |profiles: &mut ProjectionProfiles, img: &GrayImage| {
+            #[cfg(feature = "rayon")]
+            if config.enable_parallel {
+                return config.install(|| profiles.fill_parallel(img, config.parallel_chunk_len));
+            }
+            profiles.fill(img)
+        };
+        debug_span!("ink_profiles").in_scope(|| fill_profiles(profiles, binarized_img));
+
+        // Detect ruled lines and erase them, so that they do not count as ink
+        // in the other dimension
+        let (row_separators, column_separators) = match &config.separators {
+            Some(separators) => {
+                let _span = debug_span!("separators").entered();
+                let (row_separators, column_separators) =
+                    Self::find_profile_separators(profiles, separators);
+                binarize::reuse_buffer(cleaned_img, width, height);
+                cleaned_img.copy_from_slice(binarized_img);
+                for (x, y, pixel) in cleaned_img.enumerate_pixels_mut() {
+                    if row_separators[y as usize] || column_separators[x as usize] {
+                        *pixel = Luma([255]);
+                    }
+                }
+                fill_profiles(profiles, cleaned_img);
+                (row_separators, column_separators)
+            }
+            None => (Vec::new(), Vec::new()),
         };
 
+        Self::from_profiles(profiles, config, &row_separators, &column_separators)
+    }
+
+    /// Detects rows and columns from the ink profiles of an image whose separator
+    /// pixels, if any, have been erased.
+    pub(crate) fn from_profiles(
+        profiles: &ProjectionProfiles,
+        config: &GridConfig,
+        row_separators: &[bool],
+        column_separators: &[bool],
+    ) -> Result<Self, GridError> {
+        // Process rows and columns based on configuration
+        #[cfg(feature = "rayon")]
+        if config.enable_parallel {
+            let (rows, columns) = config.install(|| {
+                Self::process_lines_parallel(profiles, config, row_separators, column_separators)
+            })?;
+            return Ok(Grid { rows, columns });
+        }
+        let (rows, columns) =
+            Self::process_lines_sequential(profiles, config, row_separators, column_separators)?;
+
         Ok(Grid { rows, columns })
     }
 
+    /// Returns a copy of the grid with every row shifted by `dy` and every column by `dx`.
+    pub(crate) fn translated(&self, dx: u32, dy: u32) -> Self {
+        Grid {
+            rows: self
+                .rows
+                .iter()
+                .map(|row| Row {
+                    y: row.y + dy,
+                    ..row.clone()
+                })
+                .collect(),
+            columns: self
+                .columns
+                .iter()
+                .map(|col| Column {
+                    x: col.x + dx,
+                    ..col.clone()
+                })
+                .collect(),
+        }
+    }
+
     /// Returns an iterator over all rows in the grid.
     ///
     /// # Example
@@ -673,32 +1330,37 @@ impl Grid {
     }
 
     /// Process image lines in parallel using rayon.
+    ///
+    /// Rows and columns are processed concurrently, and each of them is split
+    /// into chunks of [`GridConfig::parallel_chunk_len`] lines whose runs are
+    /// stitched together, so the result is identical to sequential processing.
+    #[cfg(feature = "rayon")]
     fn process_lines_parallel(
-        img: &GrayImage,
-        merge_threshold_ratio: f32,
+        profiles: &ProjectionProfiles,
+        config: &GridConfig,
+        row_separators: &[bool],
+        column_separators: &[bool],
     ) -> Result<(SmallVecLine<Row>, SmallVecLine<Column>), GridError> {
         trace!("Processing lines in parallel");
-        let (width, height) = img.dimensions();
+        let (width, height) = (profiles.width(), profiles.height());
 
-        // Process rows and columns in parallel
+        // Process rows and columns in parallel, under the span of the caller
+        let parent = Span::current();
         let result = rayon::join(
             || {
-                Self::process_dimension::<Row>(
-                    img,
-                    height,
-                    width,
-                    merge_threshold_ratio,
-                    Self::is_row_empty,
-                )
+                debug_span!(parent: &parent, "row_pass").in_scope(|| {
+                    Self::process_profile::<Row>(&profiles.rows, width, config, row_separators)
+                })
             },
             || {
-                Self::process_dimension::<Column>(
-                    img,
-                    width,
-                    height,
-                    merge_threshold_ratio,
-                    Self::is_column_empty,
-                )
+                debug_span!(parent: &parent, "column_pass").in_scope(|| {
+                    Self::process_profile::<Column>(
+                        &profiles.columns,
+                        height,
+                        config,
+                        column_separators,
+                    )
+                })
             },
         );
 
@@ -711,29 +1373,196 @@ impl Grid {
 
     /// Process image lines sequentially.
     fn process_lines_sequential(
-        img: &GrayImage,
-        merge_threshold_ratio: f32,
+        profiles: &ProjectionProfiles,
+        config: &GridConfig,
+        row_separators: &[bool],
+        column_separators: &[bool],
     ) -> Result<(SmallVecLine<Row>, SmallVecLine<Column>), GridError> {
         trace!("Processing lines sequentially");
-        let (width, height) = img.dimensions();
+        let (width, height) = (profiles.width(), profiles.height());
 
         // Process rows first, then columns
-        let rows = Self::process_dimension::<Row>(
-            img,
-            height,
-            width,
-            merge_threshold_ratio,
-            Self::is_row_empty,
-        )?;
-        let columns = Self::process_dimension::<Column>(
-            img,
+        let rows = debug_span!("row_pass").in_scope(|| {
+            Self::process_profile::<Row>(&profiles.rows, width, config, row_separators)
+        })?;
+        let columns = debug_span!("column_pass").in_scope(|| {
+            Self::process_profile::<Column>(&profiles.columns, height, config, column_separators)
+        })?;
+
+        Ok((rows, columns))
+    }
+
+    /// Segments the ink profile of a dimension into lines.
+    fn process_profile<T: LineTrait>(
+        profile: &[u32],
+        secondary_dim: u32,
+        config: &GridConfig,
+        separators: &[bool],
+    ) -> Result<SmallVecLine<T>, GridError> {
+        let primary_dim = profile.len() as u32;
+        debug!(
+            "Processing profile with primary_dim={}, secondary_dim={}",
+            primary_dim, secondary_dim
+        );
+        if primary_dim == 0 || secondary_dim == 0 {
+            return Err(GridError::InvalidDimensions {
+                width: secondary_dim,
+                height: primary_dim,
+            });
+        }
+
+        let classify = |i| {
+            Self::classify_line(separators, i, || {
+                profile[i as usize].classify(secondary_dim, config)
+            })
+        };
+        #[cfg(feature = "rayon")]
+        let runs = if config.enable_parallel {
+            Self::collect_runs_parallel(primary_dim, config.parallel_chunk_len, classify)
+        } else {
+            Self::collect_runs(primary_dim, classify)
+        };
+        #[cfg(not(feature = "rayon"))]
+        let runs = Self::collect_runs(primary_dim, classify);
+        let lines = Self::segment_runs(runs, config);
+        Ok(lines.into_iter().map(T::new).collect())
+    }
+
+    /// Classifies a line as a separator if it is flagged as one, otherwise
+    /// with `classify`.
+    fn classify_line(separators: &[bool], i: u32, classify: impl FnOnce() -> LineKind) -> LineKind {
+        if separators.get(i as usize).copied().unwrap_or(false) {
+            LineKind::Separator
+        } else {
+            classify()
+        }
+    }
+
+    /// Classifies a row from its ink pixels.
+    ///
+    /// # Arguments
+    /// * `img` - The binarized image.
+    /// * `y` - The y-coordinate of the row.
+    /// * `width` - The width of the image.
+    /// * `config` - The configuration providing the ink tolerance and sparse ratio.
+    ///
+    /// # Returns
+    /// [`LineKind::Empty`] if the row has no more ink than tolerated,
+    /// [`LineKind::Sparse`] if its ink ratio is below [`GridConfig::sparse_ratio`],
+    /// otherwise [`LineKind::Full`].
+    ///
+    /// # Example
+    /// ```
+    /// use grider::{Grid, GridConfig, LineKind};
+    /// use image::{GrayImage, Luma};
+    ///
+    /// let mut img = GrayImage::from_pixel(10, 1, Luma([255u8]));
+    /// img.put_pixel(3, 0, Luma([0u8]));
+    /// let config = GridConfig {
+    ///     sparse_ratio: Some(0.2),
+    ///     ..Default::default()
+    /// };
+    /// assert_eq!(Grid::classify_row(&img, 0, 10, &config), LineKind::Sparse);
+    /// ```
+    pub fn classify_row(img: &GrayImage, y: u32, width: u32, config: &GridConfig) -> LineKind {
+        Self::classify_ink(
+            (0..width).map(|x| img.get_pixel(x, y).channels()[0] != 255),
             width,
+            config,
+        )
+    }
+
+    /// Classifies a column from its ink pixels.
+    ///
+    /// See [`Grid::classify_row`] for details.
+    pub fn classify_column(img: &GrayImage, x: u32, height: u32, config: &GridConfig) -> LineKind {
+        Self::classify_ink(
+            (0..height).map(|y| img.get_pixel(x, y).channels()[0] != 255),
             height,
-            merge_threshold_ratio,
-            Self::is_column_empty,
-        )?;
+            config,
+        )
+    }
 
-        Ok((rows, columns))
+    /// Classifies a line from its number of ink pixels.
+    ///
+    /// # Example
+    /// ```
+    /// use grider::{Grid, GridConfig, InkTolerance, LineKind};
+    ///
+    /// let config = GridConfig {
+    ///     ink_tolerance: InkTolerance::Pixels(1),
+    ///     ..Default::default()
+    /// };
+    /// assert_eq!(Grid::classify_ink_count(1, 10, &config), LineKind::Empty);
+    /// assert_eq!(Grid::classify_ink_count(2, 10, &config), LineKind::Full);
+    /// ```
+    pub fn classify_ink_count(ink: u32, length: u32, config: &GridConfig) -> LineKind {
+        if ink <= config.ink_tolerance.max_ink(length) {
+            LineKind::Empty
+        } else if config
+            .sparse_ratio
+            .is_some_and(|sparse_ratio| (ink as f32) < sparse_ratio * length as f32)
+        {
+            LineKind::Sparse
+        } else {
+            LineKind::Full
+        }
+    }
+
+    /// Classifies a line from an iterator telling whether each of its pixels is ink.
+    fn classify_ink(
+        pixels: impl Iterator<Item = bool>,
+        length: u32,
+        config: &GridConfig,
+    ) -> LineKind {
+        let max_ink = config.ink_tolerance.max_ink(length) as usize;
+        let mut ink = pixels.filter(|&is_ink| is_ink);
+        match config.sparse_ratio {
+            Some(_) => Self::classify_ink_count(ink.count() as u32, length, config),
+            // Without sparse lines, stop at the first pixel over the tolerance
+            None if ink.nth(max_ink).is_none() => LineKind::Empty,
+            None => LineKind::Full,
+        }
+    }
+
+    /// Finds the row and column separators from the ink profiles of an image.
+    pub(crate) fn find_profile_separators(
+        profiles: &ProjectionProfiles,
+        separators: &SeparatorConfig,
+    ) -> (Vec<bool>, Vec<bool>) {
+        let (width, height) = (profiles.width(), profiles.height());
+        (
+            Self::find_separators(height, width, separators, |y| profiles.rows[y as usize]),
+            Self::find_separators(width, height, separators, |x| profiles.columns[x as usize]),
+        )
+    }
+
+    /// Finds ruled lines along a dimension: runs of solid lines no thicker than
+    /// the configured maximum.
+    ///
+    /// # Returns
+    /// One flag per line telling whether it belongs to a separator.
+    fn find_separators(
+        primary_dim: u32,
+        secondary_dim: u32,
+        separators: &SeparatorConfig,
+        ink_fn: impl Fn(u32) -> u32,
+    ) -> Vec<bool> {
+        trace!("Finding separators");
+        let mut flags: Vec<bool> = (0..primary_dim)
+            .map(|i| separators.is_solid(ink_fn(i), secondary_dim))
+            .collect();
+
+        // Solid runs too thick to be ruling lines are content
+        let mut start = 0;
+        while start < flags.len() {
+            let run = flags[start..].iter().take_while(|&&solid| solid).count();
+            if run as u32 > separators.max_thickness {
+                flags[start..start + run].fill(false);
+            }
+            start += run.max(1);
+        }
+        flags
     }
 
     /// Generic function to process a dimension (rows or columns).
@@ -743,6 +1572,34 @@ impl Grid {
         secondary_dim: u32,
         merge_threshold_ratio: f32,
         is_empty_fn: impl Fn(&GrayImage, u32, u32) -> bool + Sync,
+    ) -> Result<SmallVecLine<T>, GridError> {
+        let config = GridConfig {
+            merge_threshold_ratio,
+            ..Default::default()
+        };
+        Self::process_dimension_with_config(
+            img,
+            primary_dim,
+            secondary_dim,
+            &config,
+            |img, i, secondary_dim| {
+                if is_empty_fn(img, i, secondary_dim) {
+                    LineKind::Empty
+                } else {
+                    LineKind::Full
+                }
+            },
+        )
+    }
+
+    /// Generic function to process a dimension (rows or columns) with a line
+    /// classifier, honoring the full configuration.
+    pub fn process_dimension_with_config<T: LineTrait + Send>(
+        img: &GrayImage,
+        primary_dim: u32,
+        secondary_dim: u32,
+        config: &GridConfig,
+        classify_fn: impl Fn(&GrayImage, u32, u32) -> LineKind + Sync,
     ) -> Result<SmallVecLine<T>, GridError> {
         debug!(
             "Processing dimension with primary_dim={}, secondary_dim={}",
@@ -755,18 +1612,61 @@ impl Grid {
             });
         }
 
-        // Collect initial lines
-        let lines = Self::collect_lines(img, primary_dim, secondary_dim, &is_empty_fn)
-            .map_err(|e| GridError::LineDetectionError(e.to_string()))?;
+        let merged =
+            Self::segment_classified(primary_dim, config, |i| classify_fn(img, i, secondary_dim));
+        Ok(merged.into_iter().map(T::new).collect())
+    }
 
-        // Calculate threshold for merging
-        let average_size = Self::calculate_average_line_size(&lines);
-        let merge_threshold = (average_size as f32 * merge_threshold_ratio) as u32;
+    /// Collects the runs of classified lines and merges the small ones, as configured.
+    pub(crate) fn segment_classified(
+        primary_dim: u32,
+        config: &GridConfig,
+        classify: impl Fn(u32) -> LineKind,
+    ) -> SmallVecLine<LineInfo> {
+        Self::segment_runs(Self::collect_runs(primary_dim, classify), config)
+    }
 
-        // Merge small lines and convert to final type
-        let merged = Self::merge_small_lines(lines, merge_threshold);
+    /// Constrains the collected runs and merges the small ones, as configured.
+    pub(crate) fn segment_runs(runs: Vec<LineInfo>, config: &GridConfig) -> SmallVecLine<LineInfo> {
+        let lines = Self::constrain_runs(runs, config);
 
-        Ok(merged.into_iter().map(T::new).collect())
+        // Merge small lines
+        debug_span!("merge").in_scope(|| Self::merge_small_lines_with_config(lines, config))
+    }
+
+    /// Merges small lines using the merge threshold, policy and direction of the
+    /// configuration.
+    pub(crate) fn merge_small_lines_with_config(
+        lines: Vec<LineInfo>,
+        config: &GridConfig,
+    ) -> SmallVecLine<LineInfo> {
+        // Calculate thresholds for merging
+        let thresholds = config
+            .merge_threshold
+            .compute(&lines, config.merge_threshold_ratio);
+
+        Self::merge_small_lines_with_thresholds(lines, thresholds, config)
+    }
+
+    /// Merges small lines using the thresholds for empty and content lines, in
+    /// that order, and the merge policy and direction of the configuration.
+    pub(crate) fn merge_small_lines_with_thresholds(
+        lines: Vec<LineInfo>,
+        (empty_threshold, content_threshold): (u32, u32),
+        config: &GridConfig,
+    ) -> SmallVecLine<LineInfo> {
+        Self::merge_small_lines_by_kind(
+            lines,
+            |kind| {
+                if kind.is_empty() {
+                    empty_threshold
+                } else {
+                    content_threshold
+                }
+            },
+            config.merge_policy,
+            config.merge_direction,
+        )
     }
 
     /// Collects initial lines without merging.
@@ -775,23 +1675,84 @@ impl Grid {
         primary_dim: u32,
         secondary_dim: u32,
         is_empty_fn: impl Fn(&GrayImage, u32, u32) -> bool,
+    ) -> Result<Vec<LineInfo>, GridError> {
+        Self::collect_classified_lines(img, primary_dim, secondary_dim, |img, i, secondary_dim| {
+            if is_empty_fn(img, i, secondary_dim) {
+                LineKind::Empty
+            } else {
+                LineKind::Full
+            }
+        })
+    }
+
+    /// Collects initial lines without merging, grouping consecutive lines of the
+    /// same kind as returned by `classify_fn`.
+    pub fn collect_classified_lines(
+        img: &GrayImage,
+        primary_dim: u32,
+        secondary_dim: u32,
+        classify_fn: impl Fn(&GrayImage, u32, u32) -> LineKind,
     ) -> Result<Vec<LineInfo>, GridError> {
         trace!("Collecting lines");
+        Ok(Self::collect_runs(primary_dim, |i| {
+            classify_fn(img, i, secondary_dim)
+        }))
+    }
+
+    /// Groups consecutive lines of the same kind into runs.
+    pub(crate) fn collect_runs(
+        primary_dim: u32,
+        classify: impl Fn(u32) -> LineKind,
+    ) -> Vec<LineInfo> {
+        Self::collect_runs_in(0..primary_dim, classify)
+    }
+
+    /// Groups consecutive lines of the same kind into runs, splitting the lines
+    /// into chunks of `chunk_len` that are classified in parallel.
+    ///
+    /// Runs crossing a chunk boundary are stitched back together, so the result
+    /// is identical to [`Grid::collect_runs`].
+    #[cfg(feature = "rayon")]
+    pub(crate) fn collect_runs_parallel(
+        primary_dim: u32,
+        chunk_len: u32,
+        classify: impl Fn(u32) -> LineKind + Sync,
+    ) -> Vec<LineInfo> {
+        let chunk_len = chunk_len.max(1);
+        let chunks: Vec<Vec<LineInfo>> = (0..primary_dim.div_ceil(chunk_len))
+            .into_par_iter()
+            .map(|chunk| {
+                let start = chunk * chunk_len;
+                let end = primary_dim.min(start.saturating_add(chunk_len));
+                Self::collect_runs_in(start..end, &classify)
+            })
+            .collect();
+
+        let mut lines: Vec<LineInfo> = Vec::with_capacity(chunks.iter().map(Vec::len).sum());
+        for line in chunks.into_iter().flatten() {
+            match lines.last_mut() {
+                Some(last) if last.kind == line.kind => last.length += line.length,
+                _ => lines.push(line),
+            }
+        }
+        lines
+    }
+
+    /// Groups consecutive lines of the same kind within `range` into runs.
+    pub(crate) fn collect_runs_in(
+        range: Range<u32>,
+        classify: impl Fn(u32) -> LineKind,
+    ) -> Vec<LineInfo> {
         let mut lines = Vec::new();
-        let mut current_start = 0;
-        let mut current_kind = if is_empty_fn(img, 0, secondary_dim) {
-            LineKind::Empty
-        } else {
-            LineKind::Full
-        };
+        if range.is_empty() {
+            return lines;
+        }
+        let mut current_start = range.start;
+        let mut current_kind = classify(range.start);
         let mut current_length = 1;
 
-        for i in 1..primary_dim {
-            let new_kind = if is_empty_fn(img, i, secondary_dim) {
-                LineKind::Empty
-            } else {
-                LineKind::Full
-            };
+        for i in range.start + 1..range.end {
+            let new_kind = classify(i);
 
             if new_kind == current_kind {
                 current_length += 1;
@@ -806,21 +1767,100 @@ impl Grid {
         // Add the final line
         lines.push(LineInfo::new(current_start, current_length, current_kind));
 
-        Ok(lines)
+        lines
     }
 
-    /// Calculate average size of lines for threshold determination.
-    fn calculate_average_line_size(lines: &[LineInfo]) -> u32 {
-        trace!("Calculating average line size");
-        if lines.is_empty() {
-            return 0;
+    /// Collects initial lines without merging, honoring the hysteresis and
+    /// minimum run lengths of the configuration.
+    ///
+    /// A run of another kind shorter than [`GridConfig::hysteresis`], or shorter
+    /// than the minimum run of its kind, does not change the current kind and is
+    /// absorbed by the previous run. Content following a gap is measured up to the
+    /// next gap, whether its lines are full or sparse. A short leading run takes
+    /// the kind of the run that follows it. Separators are never absorbed and
+    /// never absorb other runs.
+    ///
+    /// # Example
+    /// ```
+    /// use grider::{Grid, GridConfig, LineInfo, LineKind};
+    /// use image::GrayImage;
+    ///
+    /// let img = GrayImage::new(1, 10);
+    /// let config = GridConfig {
+    ///     min_full_run: 2,
+    ///     ..Default::default()
+    /// };
+    /// // A one pixel speck in the middle of a gap
+    /// let lines = Grid::collect_lines_with_config(&img, 10, 1, &config, |_, y, _| {
+    ///     if y == 5 { LineKind::Full } else { LineKind::Empty }
+    /// })
+    /// .unwrap();
+    /// assert_eq!(lines, vec![LineInfo::new(0, 10, LineKind::Empty)]);
+    /// ```
+    pub fn collect_lines_with_config(
+        img: &GrayImage,
+        primary_dim: u32,
+        secondary_dim: u32,
+        config: &GridConfig,
+        classify_fn: impl Fn(&GrayImage, u32, u32) -> LineKind,
+    ) -> Result<Vec<LineInfo>, GridError> {
+        let lines = Self::collect_classified_lines(img, primary_dim, secondary_dim, classify_fn)?;
+        Ok(Self::constrain_runs(lines, config))
+    }
+
+    /// Absorbs the runs that are too short for the hysteresis and minimum run
+    /// lengths of the configuration.
+    pub(crate) fn constrain_runs(lines: Vec<LineInfo>, config: &GridConfig) -> Vec<LineInfo> {
+        if config.hysteresis <= 1 && config.min_empty_run == 0 && config.min_full_run == 0 {
+            return lines;
         }
-        let total: u32 = lines.iter().map(|l| l.length).sum();
-        total / lines.len() as u32
+        let min_run = |kind: &LineKind| match kind {
+            LineKind::Empty => config.min_empty_run,
+            LineKind::Full | LineKind::Sparse => config.min_full_run,
+            LineKind::Separator => 0,
+        };
+
+        // Length of the content, full or sparse, from each run to the next gap
+        let mut stretches = vec![0; lines.len()];
+        for i in (0..lines.len()).rev() {
+            if lines[i].kind.has_content() {
+                stretches[i] = lines[i].length + stretches.get(i + 1).copied().unwrap_or(0);
+            }
+        }
+
+        let mut runs: Vec<LineInfo> = Vec::with_capacity(lines.len());
+        for (line, stretch) in lines.into_iter().zip(stretches) {
+            // Only a run starting the lines is leading, not the start of a window of them
+            let is_leading = runs.len() == 1 && runs[0].start == 0;
+            // Content entered from a gap is measured up to the next gap
+            let length = match runs.last() {
+                Some(last) if last.kind.has_content() => line.length,
+                _ => line.length.max(stretch),
+            };
+            match runs.last_mut() {
+                Some(last) if last.kind == line.kind => last.length += line.length,
+                Some(last) if last.kind.is_separator() || line.kind.is_separator() => {
+                    runs.push(line)
+                }
+                Some(last) if length < config.hysteresis.max(min_run(&line.kind)) => {
+                    last.length += line.length
+                }
+                Some(last) if is_leading && last.length < min_run(&last.kind) => {
+                    last.length += line.length;
+                    last.kind = line.kind;
+                }
+                _ => runs.push(line),
+            }
+        }
+        runs
     }
 
     /// Merges lines smaller than the threshold.
     ///
+    /// A small line is merged into the previous one, keeping the kind of the
+    /// first line. [`LineKind::Separator`] lines are never merged, so they always
+    /// bound the lines around them.
+    ///
     /// # Arguments
     /// * `lines` - A vector of [`LineInfo`] representing the lines.
     /// * `threshold` - The threshold for merging lines.
@@ -828,31 +1868,131 @@ impl Grid {
     /// # Returns
     /// A vector of merged [`LineInfo`].
     pub fn merge_small_lines(lines: Vec<LineInfo>, threshold: u32) -> SmallVecLine<LineInfo> {
+        Self::merge_small_lines_with_policy(
+            lines,
+            threshold,
+            MergePolicy::default(),
+            MergeDirection::default(),
+        )
+    }
+
+    /// Merges lines smaller than the threshold using the given policy and direction.
+    ///
+    /// [`LineKind::Separator`] lines are never merged.
+    ///
+    /// # Arguments
+    /// * `lines` - A vector of [`LineInfo`] representing the lines.
+    /// * `threshold` - The threshold for merging lines.
+    /// * `policy` - How the kind of merged lines is decided.
+    /// * `direction` - Which neighbor a small line is merged into.
+    ///
+    /// # Returns
+    /// A vector of merged [`LineInfo`].
+    ///
+    /// # Example
+    /// ```
+    /// use grider::{Grid, LineInfo, LineKind, MergeDirection, MergePolicy};
+    ///
+    /// let lines = vec![
+    ///     LineInfo::new(0, 2, LineKind::Empty),
+    ///     LineInfo::new(2, 20, LineKind::Full),
+    /// ];
+    /// let merged = Grid::merge_small_lines_with_policy(
+    ///     lines,
+    ///     5,
+    ///     MergePolicy::MajorityByPixels,
+    ///     MergeDirection::Previous,
+    /// );
+    /// assert_eq!(merged[0], LineInfo::new(0, 22, LineKind::Full));
+    /// ```
+    pub fn merge_small_lines_with_policy(
+        lines: Vec<LineInfo>,
+        threshold: u32,
+        policy: MergePolicy,
+        direction: MergeDirection,
+    ) -> SmallVecLine<LineInfo> {
         trace!("Merging small lines with threshold={}", threshold);
-        if lines.is_empty() {
-            return SmallVecLine::new();
-        }
-        let mut merged_lines = SmallVecLine::new();
-        let mut current_start = lines[0].start;
-        let mut current_length = lines[0].length;
-        let mut current_kind = lines[0].kind.clone();
+        Self::merge_small_lines_by_kind(lines, |_| threshold, policy, direction)
+    }
+
+    /// Merges small lines, with a threshold that depends on the kind of each line.
+    fn merge_small_lines_by_kind(
+        lines: Vec<LineInfo>,
+        threshold: impl Fn(&LineKind) -> u32,
+        policy: MergePolicy,
+        direction: MergeDirection,
+    ) -> SmallVecLine<LineInfo> {
+        trace!(
+            "Merging small lines with policy={:?}, direction={:?}",
+            policy,
+            direction
+        );
+        let groups: Vec<MergeGroup> = lines.into_iter().map(MergeGroup::new).collect();
+        let is_small = |group: &MergeGroup| group.length < threshold(&group.kind(policy));
+        let merged = match direction {
+            MergeDirection::Previous => Self::merge_into_previous(groups, is_small, policy),
+            MergeDirection::SmallerNeighbor => {
+                Self::merge_into_smaller_neighbor(groups, is_small, policy)
+            }
+        };
+        merged.iter().map(|group| group.to_line(policy)).collect()
+    }
 
-        for line in lines.into_iter().skip(1) {
-            if current_length < threshold || line.length < threshold {
+    /// Merges each small line into the previous one, in a single pass.
+    fn merge_into_previous(
+        groups: Vec<MergeGroup>,
+        is_small: impl Fn(&MergeGroup) -> bool,
+        policy: MergePolicy,
+    ) -> Vec<MergeGroup> {
+        let mut merged: Vec<MergeGroup> = Vec::with_capacity(groups.len());
+        for group in groups {
+            match merged.last_mut() {
                 // Merge with the previous line if either is smaller than the threshold
-                current_length += line.length;
-            } else {
-                // Push the merged line
-                merged_lines.push(LineInfo::new(current_start, current_length, current_kind));
-                current_start = line.start;
-                current_length = line.length;
-                current_kind = line.kind;
+                Some(current)
+                    if current.can_merge(&group, policy)
+                        && (is_small(current) || is_small(&group)) =>
+                {
+                    current.absorb(group)
+                }
+                _ => merged.push(group),
             }
         }
+        merged
+    }
 
-        // Push the last merged line
-        merged_lines.push(LineInfo::new(current_start, current_length, current_kind));
-        merged_lines
+    /// Repeatedly merges the smallest line below the threshold into its smaller neighbor.
+    fn merge_into_smaller_neighbor(
+        mut groups: Vec<MergeGroup>,
+        is_small: impl Fn(&MergeGroup) -> bool,
+        policy: MergePolicy,
+    ) -> Vec<MergeGroup> {
+        loop {
+            // Find the smallest mergeable line together with its smaller neighbor
+            let candidate = (0..groups.len())
+                .filter(|&i| is_small(&groups[i]))
+                .filter_map(|i| {
+                    let previous = i
+                        .checked_sub(1)
+                        .filter(|&j| groups[j].can_merge(&groups[i], policy));
+                    let next = Some(i + 1)
+                        .filter(|&j| j < groups.len() && groups[i].can_merge(&groups[j], policy));
+                    let neighbor = match (previous, next) {
+                        (Some(p), Some(n)) if groups[n].length < groups[p].length => n,
+                        (Some(p), _) => p,
+                        (None, Some(n)) => n,
+                        (None, None) => return None,
+                    };
+                    Some((i, neighbor))
+                })
+                .min_by_key(|&(i, _)| groups[i].length);
+
+            let Some((i, neighbor)) = candidate else {
+                return groups;
+            };
+            let group = groups.remove(i);
+            let neighbor = if neighbor > i { neighbor - 1 } else { neighbor };
+            groups[neighbor].absorb(group);
+        }
     }
 
     /// Checks if a row is empty (all pixels are white).
@@ -865,8 +2005,30 @@ impl Grid {
     /// # Returns
     /// `true` if the row is empty (fully white), otherwise `false`.
     pub fn is_row_empty(img: &GrayImage, y: u32, width: u32) -> bool {
-        trace!("Checking if row y={} is empty", y);
-        (0..width).all(|x| img.get_pixel(x, y).channels()[0] == 255)
+        Self::is_row_empty_with_tolerance(img, y, width, InkTolerance::default())
+    }
+
+    /// Checks if a row is empty, allowing up to `tolerance` ink pixels.
+    ///
+    /// The row is classified as by detection, with [`Grid::classify_ink_count`]
+    /// and no sparse lines.
+    ///
+    /// # Arguments
+    /// * `img` - The grayscale image to check.
+    /// * `y` - The y-coordinate of the row.
+    /// * `width` - The width of the image.
+    /// * `tolerance` - The amount of ink the row may contain.
+    ///
+    /// # Returns
+    /// `true` if the row contains no more ink than allowed, otherwise `false`.
+    pub fn is_row_empty_with_tolerance(
+        img: &GrayImage,
+        y: u32,
+        width: u32,
+        tolerance: InkTolerance,
+    ) -> bool {
+        let pixels = (0..width).map(|x| img.get_pixel(x, y).channels()[0] != 255);
+        Self::classify_ink(pixels, width, &Self::tolerance_config(tolerance)).is_empty()
     }
 
     /// Checks if a column is empty (all pixels are white).
@@ -879,8 +2041,38 @@ impl Grid {
     /// # Returns
     /// `true` if the column is empty (fully white), otherwise `false`.
     pub fn is_column_empty(img: &GrayImage, x: u32, height: u32) -> bool {
-        trace!("Checking if column x={} is empty", x);
-        (0..height).all(|y| img.get_pixel(x, y).channels()[0] == 255)
+        Self::is_column_empty_with_tolerance(img, x, height, InkTolerance::default())
+    }
+
+    /// Checks if a column is empty, allowing up to `tolerance` ink pixels.
+    ///
+    /// The column is classified as by detection, with
+    /// [`Grid::classify_ink_count`] and no sparse lines.
+    ///
+    /// # Arguments
+    /// * `img` - The grayscale image to check.
+    /// * `x` - The x-coordinate of the column.
+    /// * `height` - The height of the image.
+    /// * `tolerance` - The amount of ink the column may contain.
+    ///
+    /// # Returns
+    /// `true` if the column contains no more ink than allowed, otherwise `false`.
+    pub fn is_column_empty_with_tolerance(
+        img: &GrayImage,
+        x: u32,
+        height: u32,
+        tolerance: InkTolerance,
+    ) -> bool {
+        let pixels = (0..height).map(|y| img.get_pixel(x, y).channels()[0] != 255);
+        Self::classify_ink(pixels, height, &Self::tolerance_config(tolerance)).is_empty()
+    }
+
+    /// Returns the default configuration with the given ink tolerance.
+    fn tolerance_config(tolerance: InkTolerance) -> GridConfig {
+        GridConfig {
+            ink_tolerance: tolerance,
+            ..Default::default()
+        }
     }
 }
 
@@ -16,7 +16,9 @@
 use anyhow::{Context, Result};
 use clap::Parser;
 use image::GenericImageView;
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+use tracing_subscriber::{
+    fmt::format::FmtSpan, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter,
+};
 
 /// Command-line arguments for the grid processing program.
 #[derive(Parser, Debug)]
@@ -28,9 +30,11 @@ struct Args {
 }
 
 fn main() -> Result<()> {
-    // Initialize tracing subscriber
+    // Initialize tracing subscriber, reporting the duration of each pipeline stage
+    // when its span closes (e.g. `RUST_LOG=grider=debug`)
     tracing_subscriber::registry()
         .with(EnvFilter::from_default_env())
+        .with(tracing_subscriber::fmt::layer().with_span_events(FmtSpan::CLOSE))
         .init();
 
     // Parse command-line arguments
@@ -69,7 +73,7 @@ fn main() -> Result<()> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use drawing::GridDrawingConfig;
+    use drawing::{kind_color, Drawable, GridDrawingConfig};
     use grider::*;
     use image::*;
     use insta::assert_yaml_snapshot;
@@ -90,6 +94,7 @@ mod tests {
             threshold_block_size: 8,
             merge_threshold_ratio: 0.7,
             enable_parallel: true,
+            ..Default::default()
         };
 
         let result = Grid::try_from_image_with_config(&dynamic_img, config);
@@ -517,6 +522,35 @@ mod tests {
             let total_merged_length: u32 = merged_lines.iter().map(|line| line.length).sum();
             assert_eq!(total_input_length, total_merged_length);
         }
+
+        #[test]
+        fn test_merge_small_lines_with_policy_proptest(
+            lengths in prop::collection::vec((1..50u32, prop::sample::select(&[LineKind::Empty, LineKind::Full, LineKind::Sparse])), 1..50),
+            threshold in 1..50u32,
+            policy in prop::sample::select(&[MergePolicy::KeepFirst, MergePolicy::MajorityByPixels, MergePolicy::AnyFullWins, MergePolicy::SameKindOnly]),
+            direction in prop::sample::select(&[MergeDirection::Previous, MergeDirection::SmallerNeighbor])
+        ) {
+            // Build contiguous lines from the generated lengths
+            let mut start = 0;
+            let lines: Vec<LineInfo> = lengths.into_iter()
+                .map(|(length, kind)| {
+                    let line = LineInfo::new(start, length, kind);
+                    start += length;
+                    line
+                })
+                .collect();
+
+            let merged = Grid::merge_small_lines_with_policy(lines, threshold, policy, direction);
+
+            // Merged lines stay contiguous and cover the same span
+            let mut expected_start = 0;
+            for line in merged.iter() {
+                prop_assert_eq!(line.start, expected_start);
+                expected_start += line.length;
+            }
+            prop_assert_eq!(expected_start, start);
+        }
+
         #[test]
         fn test_process_image_proptest(width in 1..100u32, height in 1..100u32) {
             // Create a grayscale image with random pixel values
@@ -543,6 +577,68 @@ mod tests {
         assert!(Grid::is_column_empty(&img, 2, 3));
     }
 
+    #[test_case(InkTolerance::Pixels(0), false)]
+    #[test_case(InkTolerance::Pixels(1), true)]
+    #[test_case(InkTolerance::Ratio(0.1), true)]
+    #[test_case(InkTolerance::Ratio(0.05), false)]
+    fn test_is_line_empty_with_tolerance(tolerance: InkTolerance, expected: bool) {
+        // A single speck in an otherwise white row and column
+        let mut img = GrayImage::from_pixel(10, 10, Luma([255]));
+        img.put_pixel(5, 0, Luma([0]));
+
+        assert_eq!(
+            Grid::is_row_empty_with_tolerance(&img, 0, 10, tolerance),
+            expected
+        );
+        assert_eq!(
+            Grid::is_column_empty_with_tolerance(&img, 5, 10, tolerance),
+            expected
+        );
+    }
+
+    #[test_case(true)]
+    #[test_case(false)]
+    fn test_ink_tolerance_keeps_gutter(enable_parallel: bool) {
+        // Two textured blocks separated by a white gutter containing a single speck
+        let mut img = GrayImage::from_fn(30, 30, |x, y| {
+            if (10..20).contains(&y) || (x + y).is_multiple_of(2) {
+                Luma([255u8])
+            } else {
+                Luma([0u8])
+            }
+        });
+        img.put_pixel(15, 15, Luma([0u8]));
+        let dynamic_img = DynamicImage::ImageLuma8(img);
+
+        let strict = Grid::try_from_image_with_config(
+            &dynamic_img,
+            GridConfig {
+                enable_parallel,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_ne!(strict.rows.len(), 3);
+
+        let tolerant = Grid::try_from_image_with_config(
+            &dynamic_img,
+            GridConfig {
+                enable_parallel,
+                ink_tolerance: InkTolerance::Pixels(1),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            tolerant.rows,
+            SmallVecLine::from_vec(vec![
+                make_line!(Row, (0, 10, LineKind::Full)),
+                make_line!(Row, (10, 10)),
+                make_line!(Row, (20, 10, LineKind::Full)),
+            ])
+        );
+    }
+
     #[test]
     fn test_process_image() {
         let img = GrayImage::from_fn(10, 10, |_x, y| {
@@ -598,6 +694,7 @@ mod tests {
             threshold_block_size: 3, // Small block size for small image
             merge_threshold_ratio: 0.5,
             enable_parallel: false, // Sequential processing for small images
+            ..Default::default()
         };
 
         let grid = Grid::try_from_image_with_config(&dynamic_img, config).unwrap();
@@ -653,6 +750,7 @@ mod tests {
                 threshold_block_size,
                 merge_threshold_ratio,
                 enable_parallel: true,
+                ..Default::default()
             };
 
             let grid = Grid::try_from_image_with_config(&dynamic_img, config).unwrap();
@@ -667,6 +765,127 @@ mod tests {
             prop_assert_eq!(total_row_height, height);
             prop_assert_eq!(total_column_width, width);
         }
+
+        #[test]
+        fn test_parallel_chunks_match_sequential(
+            width in 1..120u32,
+            height in 1..120u32,
+            parallel_chunk_len in 1..40u32,
+            hysteresis in 0..4u32,
+            separators in any::<bool>(),
+        ) {
+            // Sparse specks, with ruled lines for the separators
+            let img = GrayImage::from_fn(width, height, |x, y| {
+                if x % 37 == 0 || y % 29 == 0 || rand::random::<u8>() < 8 {
+                    Luma([0])
+                } else {
+                    Luma([255])
+                }
+            });
+            let dynamic_img = DynamicImage::ImageLuma8(img);
+
+            let sequential = GridConfig {
+                enable_parallel: false,
+                binarizer: Binarizer::Global(128),
+                hysteresis,
+                separators: separators.then(SeparatorConfig::default),
+                ..Default::default()
+            };
+            let parallel = GridConfig {
+                enable_parallel: true,
+                parallel_chunk_len,
+                ..sequential.clone()
+            };
+
+            prop_assert_eq!(
+                Grid::try_from_image_with_config(&dynamic_img, parallel).unwrap(),
+                Grid::try_from_image_with_config(&dynamic_img, sequential).unwrap()
+            );
+        }
+    }
+
+    /// Returns the rayon index and the name of the thread on which each row and
+    /// column pass started, recording them from the first call on.
+    #[cfg(feature = "rayon")]
+    fn pass_threads() -> Vec<(Option<usize>, String)> {
+        use std::sync::{Mutex, Once};
+        use tracing::span::{Attributes, Id};
+        use tracing_subscriber::{filter::filter_fn, layer::Context, Layer};
+
+        static THREADS: Mutex<Vec<(Option<usize>, String)>> = Mutex::new(Vec::new());
+        static INSTALL: Once = Once::new();
+
+        struct PassThreads;
+        impl<S: tracing::Subscriber> Layer<S> for PassThreads {
+            fn on_new_span(&self, _: &Attributes<'_>, _: &Id, _: Context<'_, S>) {
+                let thread = std::thread::current();
+                let name = thread.name().unwrap_or_default().to_owned();
+                THREADS
+                    .lock()
+                    .unwrap()
+                    .push((rayon::current_thread_index(), name));
+            }
+        }
+
+        INSTALL.call_once(|| {
+            let passes =
+                filter_fn(|metadata| matches!(metadata.name(), "row_pass" | "column_pass"));
+            tracing_subscriber::registry()
+                .with(PassThreads.with_filter(passes))
+                .init();
+        });
+        THREADS.lock().unwrap().clone()
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test_case(1)]
+    #[test_case(3)]
+    fn test_thread_pool_injection(num_threads: usize) {
+        let img = open("tests/large.png").unwrap();
+        let sequential = GridConfig {
+            enable_parallel: false,
+            parallel_chunk_len: 16,
+            separators: Some(SeparatorConfig::default()),
+            ..Default::default()
+        };
+        let expected = Grid::try_from_image_with_config(&img, sequential.clone()).unwrap();
+        let prefix = format!("injected-{num_threads}-");
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(num_threads)
+            .thread_name({
+                let prefix = prefix.clone();
+                move |i| format!("{prefix}{i}")
+            })
+            .build()
+            .unwrap();
+        let config = GridConfig {
+            enable_parallel: true,
+            thread_pool: Some(std::sync::Arc::new(pool)),
+            ..sequential
+        };
+
+        // Both passes of every detection run on the threads of the injected pool
+        let pool_passes = || -> Vec<usize> {
+            pass_threads()
+                .into_iter()
+                .filter(|(_, name)| name.starts_with(&prefix))
+                .map(|(index, name)| index.unwrap_or_else(|| panic!("{name} is not in a pool")))
+                .collect()
+        };
+        let before = pool_passes().len();
+        assert_eq!(
+            Grid::try_from_image_with_config(&img, config.clone()).unwrap(),
+            expected
+        );
+        let passes = pool_passes();
+        assert_eq!(passes.len(), before + 2);
+        assert!(passes.iter().all(|&index| index < num_threads));
+
+        // Detection may also be started from a thread of the pool itself
+        let pool = config.thread_pool.clone().unwrap();
+        let mut detector = GridDetector::new(config);
+        assert_eq!(pool.install(|| detector.detect(&img)).unwrap(), expected);
+        assert_eq!(pool_passes().len(), before + 4);
     }
 
     #[test]
@@ -686,11 +905,13 @@ mod tests {
                 threshold_block_size: 5,
                 merge_threshold_ratio: 0.3,
                 enable_parallel: false,
+                ..Default::default()
             },
             GridConfig {
                 threshold_block_size: 10,
                 merge_threshold_ratio: 0.8,
                 enable_parallel: true,
+                ..Default::default()
             },
         ];
 
@@ -734,6 +955,1380 @@ mod tests {
         });
     }
 
+    #[test_case(Binarizer::Adaptive)]
+    #[test_case(Binarizer::Global(128))]
+    #[test_case(Binarizer::Otsu)]
+    #[test_case(Binarizer::Sauvola { k: 0.2, r: 128.0 })]
+    #[test_case(Binarizer::Niblack { k: -0.2 })]
+    fn test_binarizers_detect_stroke(binarizer: Binarizer) {
+        // A thin horizontal stroke on a white background
+        let img = GrayImage::from_fn(20, 20, |x, y| {
+            if (8..10).contains(&y) && (2..18).contains(&x) {
+                Luma([0u8])
+            } else {
+                Luma([255u8])
+            }
+        });
+        let config = GridConfig {
+            binarizer,
+            merge_threshold_ratio: 0.1,
+            ..Default::default()
+        };
+
+        let grid =
+            Grid::try_from_image_with_config(&DynamicImage::ImageLuma8(img), config).unwrap();
+
+        let expected_grid = make_grid! {
+            rows: [
+                (0, 8),
+                (8, 2, LineKind::Full),
+                (10, 10),
+            ],
+            columns: [
+                (0, 2),
+                (2, 16, LineKind::Full),
+                (18, 2),
+            ]
+        };
+        assert_eq!(grid, expected_grid);
+    }
+
+    #[test_case(BackgroundDetection::Border, false)]
+    #[test_case(BackgroundDetection::Histogram, false)]
+    #[test_case(BackgroundDetection::None, true)]
+    fn test_dark_background(background: BackgroundDetection, invert: bool) {
+        // A light stroke on a dark background, as found in dark-mode screenshots
+        let img = GrayImage::from_fn(20, 20, |x, y| {
+            if (8..10).contains(&y) && (2..18).contains(&x) {
+                Luma([230u8])
+            } else {
+                Luma([20u8])
+            }
+        });
+        let dynamic_img = DynamicImage::ImageLuma8(img);
+        let config = GridConfig {
+            merge_threshold_ratio: 0.1,
+            ..Default::default()
+        };
+
+        let naive = Grid::try_from_image_with_config(&dynamic_img, config.clone()).unwrap();
+        let grid = Grid::try_from_image_with_config(
+            &dynamic_img,
+            GridConfig {
+                background,
+                invert,
+                ..config
+            },
+        )
+        .unwrap();
+
+        let expected_grid = make_grid! {
+            rows: [
+                (0, 8),
+                (8, 2, LineKind::Full),
+                (10, 10),
+            ],
+            columns: [
+                (0, 2),
+                (2, 16, LineKind::Full),
+                (18, 2),
+            ]
+        };
+        assert_ne!(naive, expected_grid);
+        assert_eq!(grid, expected_grid);
+    }
+
+    #[test]
+    fn test_alpha_cutoff_slices_sprite_sheet() {
+        // A 2x2 sprite atlas: opaque black sprites separated by transparent gutters
+        let img = RgbaImage::from_fn(20, 20, |x, y| {
+            if (8..12).contains(&x) || (8..12).contains(&y) {
+                Rgba([0, 0, 0, 0])
+            } else {
+                Rgba([0, 0, 0, 255])
+            }
+        });
+        let dynamic_img = DynamicImage::ImageRgba8(img);
+        let config = GridConfig {
+            merge_threshold_ratio: 0.1,
+            ..Default::default()
+        };
+
+        let naive = Grid::try_from_image_with_config(&dynamic_img, config.clone()).unwrap();
+        let grid = Grid::try_from_image_with_config(
+            &dynamic_img,
+            GridConfig {
+                alpha_cutoff: Some(1),
+                ..config
+            },
+        )
+        .unwrap();
+
+        let expected_grid = make_grid! {
+            rows: [
+                (0, 8, LineKind::Full),
+                (8, 4),
+                (12, 8, LineKind::Full),
+            ],
+            columns: [
+                (0, 8, LineKind::Full),
+                (8, 4),
+                (12, 8, LineKind::Full),
+            ]
+        };
+        assert_ne!(naive, expected_grid);
+        assert_eq!(grid, expected_grid);
+    }
+
+    #[test]
+    fn test_color_key_slices_sprite_sheet() {
+        // A 2x2 sprite atlas on a slightly noisy magenta key color
+        let img = RgbImage::from_fn(20, 20, |x, y| {
+            if (8..12).contains(&x) || (8..12).contains(&y) {
+                Rgb([255, (x % 3) as u8, 250])
+            } else {
+                Rgb([0, 128, 0])
+            }
+        });
+        let config = GridConfig {
+            merge_threshold_ratio: 0.1,
+            color_key: Some(ColorKey::new(Rgb([255, 0, 255]), 5)),
+            ..Default::default()
+        };
+
+        let grid = Grid::try_from_image_with_config(&DynamicImage::ImageRgb8(img), config).unwrap();
+
+        let expected_grid = make_grid! {
+            rows: [
+                (0, 8, LineKind::Full),
+                (8, 4),
+                (12, 8, LineKind::Full),
+            ],
+            columns: [
+                (0, 8, LineKind::Full),
+                (8, 4),
+                (12, 8, LineKind::Full),
+            ]
+        };
+        assert_eq!(grid, expected_grid);
+    }
+
+    #[test]
+    fn test_alpha_cutoff_without_alpha_channel() {
+        // Images without transparency fall back to luma thresholding
+        let img = create_test_image(10, 10, "checkerboard");
+        let config = GridConfig {
+            alpha_cutoff: Some(128),
+            ..Default::default()
+        };
+
+        let grid = Grid::try_from_image_with_config(&img, config).unwrap();
+        assert_eq!(
+            grid,
+            Grid::try_from_image_with_config(&img, GridConfig::default()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_otsu_keeps_flat_panel_whole() {
+        // A flat gray panel, as found in UI screenshots
+        let img = GrayImage::from_fn(30, 30, |x, y| {
+            if (5..25).contains(&x) && (5..25).contains(&y) {
+                Luma([100u8])
+            } else {
+                Luma([255u8])
+            }
+        });
+        let config = GridConfig {
+            binarizer: Binarizer::Otsu,
+            merge_threshold_ratio: 0.1,
+            ..Default::default()
+        };
+
+        let grid =
+            Grid::try_from_image_with_config(&DynamicImage::ImageLuma8(img), config).unwrap();
+
+        assert_eq!(
+            grid.rows,
+            SmallVecLine::from_vec(vec![
+                make_line!(Row, (0, 5)),
+                make_line!(Row, (5, 20, LineKind::Full)),
+                make_line!(Row, (25, 5)),
+            ])
+        );
+    }
+
+    /// Creates a 2x2 bordered table with 1px rules and textured cell content.
+    fn create_bordered_table() -> DynamicImage {
+        let img = GrayImage::from_fn(41, 41, |x, y| {
+            let is_rule = x % 20 == 0 || y % 20 == 0;
+            let in_content = (6..14).contains(&(x % 20)) && (6..14).contains(&(y % 20));
+            if is_rule || (in_content && !(x + y).is_multiple_of(2)) {
+                Luma([0u8])
+            } else {
+                Luma([255u8])
+            }
+        });
+        DynamicImage::ImageLuma8(img)
+    }
+
+    #[test]
+    fn test_views_of_any_pixel_type() {
+        let img = create_bordered_table();
+        let config = GridConfig {
+            separators: Some(SeparatorConfig::default()),
+            ..Default::default()
+        };
+        let expected = Grid::try_from_image_with_config(&img, config.clone()).unwrap();
+
+        let luma16 = img.to_luma16();
+        let rgb32f = img.to_rgb32f();
+        let rgba16 = img.to_rgba16();
+        assert_eq!(
+            Grid::try_from_view_with_config(&luma16, config.clone()).unwrap(),
+            expected
+        );
+        assert_eq!(
+            Grid::try_from_view_with_config(&rgb32f, config.clone()).unwrap(),
+            expected
+        );
+        assert_eq!(
+            Grid::try_from_view_with_config(&rgba16, config.clone()).unwrap(),
+            expected
+        );
+        assert_eq!(
+            Grid::try_from_image_with_config(&DynamicImage::ImageRgba16(rgba16), config).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_color_images_match_their_luma8_conversion() {
+        // The gray levels of tests/large.png are sensitive to rounding
+        let rgb = open("tests/large.png").unwrap().to_rgb8();
+        let config = GridConfig::default();
+        let expected = Grid::try_from_image_with_config(
+            &DynamicImage::ImageLuma8(DynamicImage::ImageRgb8(rgb.clone()).to_luma8()),
+            config.clone(),
+        )
+        .unwrap();
+        assert_eq!((expected.rows.len(), expected.columns.len()), (18, 20));
+
+        // A detector converting one image after another into its own buffer
+        let mut detector = GridDetector::new(config.clone());
+        let image = DynamicImage::ImageRgb8(rgb.clone());
+        assert_eq!(detector.detect(&image).unwrap(), expected);
+        let deep = DynamicImage::ImageRgb16(image.to_rgb16());
+        let deep_expected = Grid::try_from_image_with_config(
+            &DynamicImage::ImageLuma8(deep.to_luma8()),
+            config.clone(),
+        );
+        assert_eq!(detector.detect(&deep).unwrap(), deep_expected.unwrap());
+        assert_eq!(detector.detect(&image).unwrap(), expected);
+        assert_eq!(
+            Grid::try_from_view_with_config(&rgb, config.clone()).unwrap(),
+            expected
+        );
+        let (width, height) = rgb.dimensions();
+        let raw = Grid::from_raw(
+            rgb.as_raw(),
+            width,
+            height,
+            width as usize * 3,
+            PixelFormat::Rgb8,
+            config,
+        );
+        assert_eq!(raw.unwrap(), expected);
+    }
+
+    #[test_case(PixelFormat::Bgra8)]
+    #[test_case(PixelFormat::Rgb8)]
+    #[test_case(PixelFormat::Rgba8)]
+    #[test_case(PixelFormat::Gray8)]
+    fn test_from_raw_strided_buffer(format: PixelFormat) {
+        let img = create_bordered_table().to_rgba8();
+        let (width, height) = img.dimensions();
+        let config = GridConfig {
+            separators: Some(SeparatorConfig::default()),
+            ..Default::default()
+        };
+
+        // Pack the frame with 7 bytes of padding after every row
+        let stride = width as usize * format.bytes_per_pixel() + 7;
+        let mut buf = vec![0xAB; stride * height as usize];
+        for (x, y, Rgba([r, g, b, a])) in img.enumerate_pixels() {
+            let offset = y as usize * stride + x as usize * format.bytes_per_pixel();
+            let bytes: &[u8] = match format {
+                PixelFormat::Bgra8 => &[*b, *g, *r, *a],
+                PixelFormat::Rgb8 => &[*r, *g, *b],
+                PixelFormat::Rgba8 => &[*r, *g, *b, *a],
+                PixelFormat::Gray8 => &[*r],
+            };
+            buf[offset..offset + bytes.len()].copy_from_slice(bytes);
+        }
+
+        let grid = Grid::from_raw(&buf, width, height, stride, format, config.clone()).unwrap();
+
+        assert_eq!(
+            grid,
+            Grid::try_from_image_with_config(&DynamicImage::ImageRgba8(img), config).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_from_raw_invalid_buffers() {
+        let config = GridConfig::default();
+        let buf = vec![255u8; 100];
+
+        assert!(matches!(
+            Grid::from_raw(&buf, 10, 0, 10, PixelFormat::Gray8, config.clone()),
+            Err(GridError::InvalidDimensions { .. })
+        ));
+        assert!(matches!(
+            Grid::from_raw(&buf, 10, 2, 20, PixelFormat::Rgb8, config.clone()),
+            Err(GridError::InvalidStride {
+                stride: 20,
+                minimum: 30
+            })
+        ));
+        assert!(matches!(
+            Grid::from_raw(&buf, 10, 3, 40, PixelFormat::Bgra8, config.clone()),
+            Err(GridError::BufferTooSmall {
+                length: 100,
+                required: 120
+            })
+        ));
+        // Strides too large to address are rejected instead of wrapping around
+        assert!(matches!(
+            Grid::from_raw(
+                &buf,
+                10,
+                3,
+                usize::MAX / 2 + 1,
+                PixelFormat::Gray8,
+                config.clone()
+            ),
+            Err(GridError::BufferTooSmall {
+                length: 100,
+                required: usize::MAX
+            })
+        ));
+        // The last row does not need padding
+        assert!(Grid::from_raw(&buf, 10, 3, 45, PixelFormat::Gray8, config).is_ok());
+    }
+
+    #[test_case(GridConfig::default() ; "default")]
+    #[test_case(GridConfig { separators: Some(SeparatorConfig::default()), ..Default::default() } ; "separators")]
+    #[test_case(GridConfig { background: BackgroundDetection::Border, ..Default::default() } ; "dark background")]
+    #[test_case(GridConfig { alpha_cutoff: Some(1), ..Default::default() } ; "alpha")]
+    fn test_detector_reuses_buffers(config: GridConfig) {
+        let table = create_bordered_table();
+        let mut inverted = table.to_luma8();
+        imageops::invert(&mut inverted);
+        let images = [
+            table.clone(),
+            DynamicImage::ImageRgba8(table.to_rgba8()),
+            create_test_image(30, 50, "dense"),
+            DynamicImage::ImageLuma8(inverted),
+            table,
+        ];
+        let mut detector = GridDetector::new(config.clone());
+
+        // Buffers left over from previous images must not leak into the next
+        for img in &images {
+            let expected = Grid::try_from_image_with_config(img, config.clone()).unwrap();
+            assert_eq!(detector.detect(img).unwrap(), expected);
+            let expected = Grid::try_from_view_with_config(img, config.clone()).unwrap();
+            assert_eq!(detector.detect_view(img).unwrap(), expected);
+        }
+        assert!(matches!(
+            detector.detect(&DynamicImage::new_luma8(0, 10)),
+            Err(GridError::InvalidDimensions { .. })
+        ));
+    }
+
+    #[test_case(GridConfig::default() ; "default")]
+    #[test_case(GridConfig { separators: Some(SeparatorConfig::default()), ..Default::default() } ; "separators")]
+    #[test_case(GridConfig { background: BackgroundDetection::Border, ..Default::default() } ; "border background")]
+    #[test_case(GridConfig { background: BackgroundDetection::Histogram, binarizer: Binarizer::Otsu, ..Default::default() } ; "histogram background otsu")]
+    #[test_case(GridConfig { binarizer: Binarizer::Sauvola { k: 0.2, r: 128.0 }, threshold_block_size: 5, ..Default::default() } ; "sauvola")]
+    #[test_case(GridConfig { alpha_cutoff: Some(1), ..Default::default() } ; "alpha")]
+    #[test_case(GridConfig { color_key: Some(ColorKey::new(Rgb([255, 255, 255]), 0)), ..Default::default() } ; "color key")]
+    fn test_from_bands_matches_in_memory(config: GridConfig) {
+        let table = create_bordered_table();
+        let mut inverted = table.to_luma8();
+        imageops::invert(&mut inverted);
+        let images = [
+            DynamicImage::ImageRgba8(table.to_rgba8()),
+            create_test_image(30, 50, "dense"),
+            DynamicImage::ImageLuma8(inverted),
+            table,
+        ];
+
+        for img in &images {
+            let expected = Grid::try_from_image_with_config(img, config.clone()).unwrap();
+            // From bands of a few rows to a single band
+            for memory_limit in [25_000, 60_000, usize::MAX] {
+                let grid = Grid::from_bands(
+                    img.width(),
+                    img.height(),
+                    img.color(),
+                    memory_limit,
+                    config.clone(),
+                    |rows| Ok(img.crop_imm(0, rows.start, img.width(), rows.len() as u32)),
+                )
+                .unwrap();
+                assert_eq!(grid, expected, "memory_limit={memory_limit}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_from_bands_errors() {
+        let img = create_bordered_table();
+        let (width, height) = img.dimensions();
+        let crop =
+            |rows: std::ops::Range<u32>| Ok(img.crop_imm(0, rows.start, width, rows.len() as u32));
+        let config = GridConfig::default();
+
+        assert!(matches!(
+            Grid::from_bands(0, height, ColorType::L8, usize::MAX, config.clone(), crop),
+            Err(GridError::InvalidDimensions { .. })
+        ));
+        assert!(matches!(
+            Grid::from_bands(width, height, ColorType::L8, 1_000, config.clone(), crop),
+            Err(GridError::MemoryLimitTooSmall { limit: 1_000, .. })
+        ));
+        assert!(matches!(
+            Grid::from_bands(
+                width,
+                height,
+                ColorType::Rgb8,
+                usize::MAX,
+                config.clone(),
+                crop
+            ),
+            Err(GridError::InvalidBand { start: 0, .. })
+        ));
+        assert!(matches!(
+            Grid::from_bands(width, height, ColorType::L8, usize::MAX, config, |_| {
+                Err(GridError::ImageConversionError("truncated".to_string()))
+            }),
+            Err(GridError::ImageConversionError(_))
+        ));
+    }
+
+    #[test]
+    fn test_from_bands_reads_bands_in_order() {
+        let img = create_bordered_table();
+        let mut reads = Vec::new();
+        let config = GridConfig {
+            binarizer: Binarizer::Global(128),
+            ..Default::default()
+        };
+
+        Grid::from_bands(
+            img.width(),
+            img.height(),
+            img.color(),
+            5_000,
+            config,
+            |rows| {
+                reads.push(rows.clone());
+                Ok(img.crop_imm(0, rows.start, img.width(), rows.len() as u32))
+            },
+        )
+        .unwrap();
+
+        // Global thresholds need no overlap, so bands tile the image exactly
+        assert!(reads.len() > 1);
+        assert_eq!(reads[0].start, 0);
+        assert_eq!(reads.last().unwrap().end, img.height());
+        assert!(reads.windows(2).all(|pair| pair[0].end == pair[1].start));
+    }
+
+    #[test_case(GridConfig::default() ; "default")]
+    #[test_case(GridConfig { enable_parallel: false, hysteresis: 3, ..Default::default() } ; "sequential hysteresis")]
+    #[test_case(GridConfig { binarizer: Binarizer::Global(128), sparse_ratio: Some(0.3), ..Default::default() } ; "global sparse")]
+    #[test_case(GridConfig { binarizer: Binarizer::Sauvola { k: 0.2, r: 128.0 }, threshold_block_size: 5, ..Default::default() } ; "sauvola")]
+    #[test_case(GridConfig { alpha_cutoff: Some(1), ..Default::default() } ; "alpha")]
+    #[test_case(GridConfig { color_key: Some(ColorKey::new(Rgb([255, 255, 255]), 0)), ..Default::default() } ; "color key")]
+    fn test_stream_matches_in_memory(config: GridConfig) {
+        let table = create_bordered_table();
+        let images = [
+            DynamicImage::ImageRgba8(table.to_rgba8()),
+            create_test_image(30, 50, "dense"),
+            open("tests/large.png").unwrap(),
+            table,
+        ];
+
+        for img in &images {
+            let expected = Grid::try_from_image_with_config(img, config.clone()).unwrap();
+            for step in [1, 7, img.height()] {
+                let mut stream = GridStream::new(img.width(), config.clone()).unwrap();
+                let mut closed = Vec::new();
+                for y in (0..img.height()).step_by(step as usize) {
+                    let rows = img.crop_imm(0, y, img.width(), step.min(img.height() - y));
+                    closed.extend_from_slice(stream.push_rows(&rows).unwrap());
+                }
+
+                // Runs are reported once, in order, as they close
+                assert_eq!(closed, stream.closed_rows(), "step={step}");
+                assert!(closed
+                    .windows(2)
+                    .all(|p| p[0].start + p[0].length == p[1].start));
+                assert_eq!(stream.finish().unwrap(), expected, "step={step}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_stream_closes_runs_early() {
+        // Black band, then white rows: the band closes once the first white row
+        // is binarized, long before the end of the capture
+        let config = GridConfig {
+            binarizer: Binarizer::Global(128),
+            ..Default::default()
+        };
+        let mut stream = GridStream::new(10, config).unwrap();
+        let black = DynamicImage::ImageLuma8(GrayImage::from_pixel(10, 5, Luma([0])));
+        let white = DynamicImage::ImageLuma8(GrayImage::from_pixel(10, 5, Luma([255])));
+
+        assert!(stream.push_rows(&black).unwrap().is_empty());
+        assert_eq!(
+            stream.push_rows(&white).unwrap(),
+            &[LineInfo::new(0, 5, LineKind::Full)]
+        );
+        assert!(stream.push_rows(&white).unwrap().is_empty());
+        assert_eq!(stream.height(), 15);
+    }
+
+    #[test]
+    fn test_stream_errors() {
+        let rows = create_bordered_table();
+        let unsupported = [
+            GridConfig {
+                binarizer: Binarizer::Otsu,
+                ..Default::default()
+            },
+            GridConfig {
+                background: BackgroundDetection::Border,
+                ..Default::default()
+            },
+            GridConfig {
+                separators: Some(SeparatorConfig::default()),
+                ..Default::default()
+            },
+        ];
+        for config in unsupported {
+            let mut stream = GridStream::new(rows.width(), config).unwrap();
+            assert!(matches!(
+                stream.push_rows(&rows),
+                Err(GridError::UnsupportedConfig(_))
+            ));
+        }
+
+        assert!(matches!(
+            GridStream::new(0, GridConfig::default()),
+            Err(GridError::InvalidDimensions { .. })
+        ));
+        let mut stream = GridStream::new(rows.width(), GridConfig::default()).unwrap();
+        stream.push_rows(&rows).unwrap();
+        assert!(matches!(
+            stream.push_rows(&DynamicImage::ImageRgb8(rows.to_rgb8())),
+            Err(GridError::InvalidBand { .. })
+        ));
+        assert!(matches!(
+            stream.push_rows(&rows.crop_imm(0, 0, 5, 5)),
+            Err(GridError::InvalidBand { .. })
+        ));
+        assert!(matches!(
+            GridStream::new(10, GridConfig::default()).unwrap().finish(),
+            Err(GridError::InvalidDimensions { .. })
+        ));
+    }
+
+    #[test_case(GridConfig::default() ; "default")]
+    #[test_case(GridConfig { enable_parallel: false, min_full_run: 2, ..Default::default() } ; "sequential min run")]
+    #[test_case(GridConfig { binarizer: Binarizer::Global(128), sparse_ratio: Some(0.3), ..Default::default() } ; "global sparse")]
+    #[test_case(GridConfig { binarizer: Binarizer::Sauvola { k: 0.2, r: 128.0 }, threshold_block_size: 5, ..Default::default() } ; "sauvola")]
+    #[test_case(GridConfig { alpha_cutoff: Some(1), ..Default::default() } ; "alpha")]
+    fn test_update_matches_full_detection(config: GridConfig) {
+        use imageproc::{drawing::draw_filled_rect_mut, rect::Rect};
+
+        let img = DynamicImage::ImageRgba8(open("tests/large.png").unwrap().to_rgba8());
+        let mut detector = GridDetector::new(config.clone());
+        let mut profiles = Grid::try_profiles_from_image_with_config(&img, config).unwrap();
+        let mut grid = detector.detect(&img).unwrap();
+        let mut frame = img.clone();
+
+        // Changes accumulate from one frame to the next, some of them clipped
+        let changes = [
+            (Rect::at(10, 10).of_size(40, 20), Rgba([0, 0, 0, 255])),
+            (Rect::at(30, 15).of_size(5, 5), Rgba([255, 255, 255, 255])),
+            (Rect::at(-5, 100).of_size(60, 3), Rgba([0, 0, 0, 0])),
+            (
+                Rect::at(img.width() as i32 - 8, -4).of_size(20, 30),
+                Rgba([90, 0, 0, 255]),
+            ),
+            (Rect::at(10, 10).of_size(40, 20), Rgba([255, 255, 255, 255])),
+        ];
+        for (dirty, color) in changes {
+            draw_filled_rect_mut(&mut frame, dirty, color);
+            grid = detector
+                .update(&frame, &grid, &mut profiles, dirty)
+                .unwrap();
+
+            assert_eq!(grid, detector.detect(&frame).unwrap(), "dirty={dirty:?}");
+            let expected =
+                Grid::try_profiles_from_image_with_config(&frame, detector.config().clone());
+            assert_eq!(profiles, expected.unwrap(), "dirty={dirty:?}");
+        }
+    }
+
+    #[test_case(GridConfig { binarizer: Binarizer::Global(128), ..Default::default() } ; "mean")]
+    #[test_case(GridConfig { binarizer: Binarizer::Global(128), merge_threshold: MergeThreshold::Uniform(LineStatistic::Absolute(6)), hysteresis: 2, min_full_run: 3, ..Default::default() } ; "absolute")]
+    #[test_case(GridConfig { binarizer: Binarizer::Global(128), merge_threshold: MergeThreshold::PerKind { empty: LineStatistic::Absolute(20), content: LineStatistic::Median }, merge_policy: MergePolicy::MajorityByPixels, ..Default::default() } ; "per kind")]
+    #[test_case(GridConfig { binarizer: Binarizer::Global(128), merge_direction: MergeDirection::SmallerNeighbor, ..Default::default() } ; "smaller neighbor")]
+    fn test_update_segments_nearby_lines(config: GridConfig) {
+        use imageproc::{drawing::draw_filled_rect_mut, rect::Rect};
+
+        // A long page of text lines 8 pixels high, 40 pixels apart
+        let page = GrayImage::from_fn(600, 4000, |x, y| {
+            let in_line = (16..24).contains(&(y % 40)) && (20..580).contains(&x);
+            Luma([if in_line && (x / 3) % 2 == 0 { 0 } else { 255 }])
+        });
+        let img = DynamicImage::ImageLuma8(page);
+        let mut detector = GridDetector::new(config.clone());
+        let mut profiles = Grid::try_profiles_from_image_with_config(&img, config).unwrap();
+        let mut grid = detector.detect(&img).unwrap();
+        let mut frame = img.to_rgba8();
+
+        let (black, white) = (Rgba([0, 0, 0, 255]), Rgba([255, 255, 255, 255]));
+        let line = |y| Rect::at(20, y).of_size(560, 8);
+        let changes = [
+            // A line moves down within its gap, then up so that the gaps above
+            // and below it swap lengths
+            (
+                Rect::at(0, 2016).of_size(600, 18),
+                vec![(line(2016), white), (line(2026), black)],
+            ),
+            (
+                Rect::at(0, 2006).of_size(600, 28),
+                vec![(line(2026), white), (line(2006), black)],
+            ),
+            // A speck, a thin rule and a block spanning several lines
+            (
+                Rect::at(300, 1000).of_size(1, 1),
+                vec![(Rect::at(300, 1000).of_size(1, 1), black)],
+            ),
+            (
+                Rect::at(0, 3001).of_size(600, 2),
+                vec![(Rect::at(0, 3001).of_size(600, 2), black)],
+            ),
+            (
+                Rect::at(100, 120).of_size(50, 150),
+                vec![(Rect::at(100, 120).of_size(50, 150), black)],
+            ),
+            // Lines merged into their neighbors at both ends of the page
+            (line(1), vec![(line(1), black)]),
+            (line(3992), vec![(line(3992), black)]),
+            (
+                Rect::at(0, 0).of_size(600, 4000),
+                vec![(Rect::at(0, 0).of_size(600, 4000), white)],
+            ),
+        ];
+        for (dirty, draws) in changes {
+            for (rect, color) in draws {
+                draw_filled_rect_mut(&mut frame, rect, color);
+            }
+            let frame = DynamicImage::ImageRgba8(frame.clone());
+            grid = detector
+                .update(&frame, &grid, &mut profiles, dirty)
+                .unwrap();
+
+            assert_eq!(grid, detector.detect(&frame).unwrap(), "dirty={dirty:?}");
+        }
+    }
+
+    #[test]
+    fn test_update_errors() {
+        use imageproc::rect::Rect;
+
+        let img = create_bordered_table();
+        let dirty = Rect::at(0, 0).of_size(5, 5);
+        let config = GridConfig::default();
+        let grid = Grid::try_from_image_with_config(&img, config.clone()).unwrap();
+        let mut profiles = Grid::try_profiles_from_image_with_config(&img, config.clone()).unwrap();
+
+        // Changes outside of the image keep the grid
+        let outside = Rect::at(100, 100).of_size(5, 5);
+        let updated =
+            grid.try_update_from_image_with_config(&img, &mut profiles, outside, config.clone());
+        assert_eq!(updated.unwrap(), grid);
+
+        let other = create_test_image(30, 50, "dense");
+        assert!(matches!(
+            grid.try_update_from_image_with_config(&other, &mut profiles, dirty, config),
+            Err(GridError::InvalidDimensions {
+                width: 30,
+                height: 50
+            })
+        ));
+        let otsu = GridConfig {
+            binarizer: Binarizer::Otsu,
+            ..Default::default()
+        };
+        assert!(matches!(
+            grid.try_update_from_image_with_config(&img, &mut profiles, dirty, otsu),
+            Err(GridError::UnsupportedConfig(_))
+        ));
+    }
+
+    #[test_case(GridConfig::default() ; "default")]
+    #[test_case(GridConfig { binarizer: Binarizer::Global(128), ..Default::default() } ; "global")]
+    #[test_case(GridConfig { binarizer: Binarizer::Global(128), invert: true, ..Default::default() } ; "global inverted")]
+    #[test_case(GridConfig { alpha_cutoff: Some(1), ..Default::default() } ; "alpha")]
+    #[test_case(GridConfig { color_key: Some(ColorKey::new(Rgb([255, 255, 255]), 0)), ..Default::default() } ; "color key")]
+    fn test_pyramid_keeps_exact_boundaries(config: GridConfig) {
+        // Textured blocks at offsets that are not multiples of the scale, with
+        // gaps wider than the scale
+        let img = GrayImage::from_fn(301, 203, |x, y| {
+            let in_block = (13..97).contains(&(x % 120)) && (7..61).contains(&(y % 70));
+            if in_block && !(x + y).is_multiple_of(2) {
+                Luma([if config.invert { 255 } else { 0 }])
+            } else {
+                Luma([if config.invert { 0 } else { 255 }])
+            }
+        });
+        let img = DynamicImage::ImageRgba8(DynamicImage::ImageLuma8(img).to_rgba8());
+        let expected = Grid::try_from_image_with_config(&img, config.clone()).unwrap();
+
+        for scale in [1, 2, 4] {
+            let pyramid = GridConfig {
+                pyramid: Some(PyramidConfig {
+                    scale,
+                    ..Default::default()
+                }),
+                ..config.clone()
+            };
+            let grid = Grid::try_from_image_with_config(&img, pyramid).unwrap();
+            assert_eq!(grid, expected, "scale={scale}");
+        }
+    }
+
+    #[test]
+    fn test_pyramid_keeps_specks() {
+        // A one pixel speck between the boundaries of a single coarse block
+        let mut img = GrayImage::from_pixel(32, 29, Luma([255]));
+        img.put_pixel(4, 8, Luma([0]));
+        let img = DynamicImage::ImageLuma8(img);
+        let config = GridConfig {
+            binarizer: Binarizer::Global(128),
+            merge_threshold_ratio: 0.0,
+            ..Default::default()
+        };
+        let full = Grid::try_from_image_with_config(&img, config.clone()).unwrap();
+        let rows: Vec<_> = full.rows.iter().map(|row| (row.y, row.height)).collect();
+        assert_eq!(rows, [(0, 8), (8, 1), (9, 20)]);
+
+        let pyramid = GridConfig {
+            pyramid: Some(PyramidConfig {
+                scale: 4,
+                refine_window: 4,
+            }),
+            ..config
+        };
+        let grid = Grid::try_from_image_with_config(&img, pyramid).unwrap();
+        assert_eq!(grid, full);
+
+        assert!(matches!(
+            Grid::try_from_image_with_config(
+                &img,
+                GridConfig {
+                    binarizer: Binarizer::Otsu,
+                    pyramid: Some(PyramidConfig::default()),
+                    ..Default::default()
+                }
+            ),
+            Err(GridError::UnsupportedConfig(_))
+        ));
+    }
+
+    #[test]
+    fn test_pyramid_entry_points() {
+        let config = GridConfig {
+            binarizer: Binarizer::Global(128),
+            ..Default::default()
+        };
+        let pyramid = GridConfig {
+            pyramid: Some(PyramidConfig::default()),
+            ..config.clone()
+        };
+
+        // Gaps wider than twice the scale: the same grid as at full resolution
+        let large = open("tests/large.png").unwrap();
+        let full = Grid::try_from_image_with_config(&large, config.clone()).unwrap();
+        let grid = GridDetector::new(pyramid.clone()).detect(&large).unwrap();
+        assert_eq!(grid, full);
+
+        // A one pixel gap below a speck is lost at 1/4 scale, but not by the
+        // entry points that ignore the pyramid
+        let img = open("tests/small.png").unwrap();
+        let full = Grid::try_from_image_with_config(&img, config.clone()).unwrap();
+        let coarse = Grid::try_from_image_with_config(&img, pyramid.clone()).unwrap();
+        assert_ne!(coarse, full);
+
+        let rgba = img.to_rgba8();
+        let view = Grid::try_from_view_with_config(&rgba, pyramid.clone());
+        assert_eq!(view.unwrap(), full);
+        let (width, height) = rgba.dimensions();
+        let raw = Grid::from_raw(
+            rgba.as_raw(),
+            width,
+            height,
+            width as usize * 4,
+            PixelFormat::Rgba8,
+            pyramid.clone(),
+        );
+        assert_eq!(raw.unwrap(), full);
+        let bands = Grid::from_bands(
+            width,
+            height,
+            img.color(),
+            1 << 20,
+            pyramid.clone(),
+            |rows| Ok(img.crop_imm(0, rows.start, width, rows.len() as u32)),
+        );
+        assert_eq!(bands.unwrap(), full);
+        let mut stream = GridStream::new(width, pyramid.clone()).unwrap();
+        stream.push_rows(&img).unwrap();
+        assert_eq!(stream.finish().unwrap(), full);
+        let tree = Grid::try_tree_from_image_with_config(&img, pyramid.clone()).unwrap();
+        assert_eq!(tree.grid, full);
+    }
+
+    fn create_masked_layout() -> (DynamicImage, RegionMask) {
+        use imageproc::rect::Rect;
+        use std::sync::Arc;
+
+        let img = GrayImage::from_fn(301, 203, |x, y| {
+            let in_block = (13..97).contains(&(x % 120)) && (7..61).contains(&(y % 70));
+            Luma([if in_block && !(x + y).is_multiple_of(3) {
+                0
+            } else {
+                255
+            }])
+        });
+        let keep = GrayImage::from_fn(301, 203, |x, y| {
+            Luma([if x < 60 && y >= 150 { 0 } else { 1 }])
+        });
+        let mask = RegionMask {
+            roi: Some(Rect::at(5, 20).of_size(280, 170)),
+            exclusions: vec![
+                Rect::at(150, 80).of_size(40, 40),
+                Rect::at(-10, 70).of_size(120, 9),
+            ],
+            image: Some(Arc::new(keep)),
+        };
+        (
+            DynamicImage::ImageRgba8(DynamicImage::ImageLuma8(img).to_rgba8()),
+            mask,
+        )
+    }
+
+    #[test]
+    fn test_mask_counts_as_empty() {
+        let (img, mask) = create_masked_layout();
+        let config = GridConfig {
+            binarizer: Binarizer::Global(128),
+            ..Default::default()
+        };
+
+        // With a global threshold, masking is the same as painting the pixels white
+        let mut painted = img.to_rgba8();
+        for (x, y, pixel) in painted.enumerate_pixels_mut() {
+            if mask.is_masked(x, y) {
+                *pixel = Rgba([255, 255, 255, 255]);
+            }
+        }
+        let expected =
+            Grid::try_from_image_with_config(&DynamicImage::ImageRgba8(painted), config.clone())
+                .unwrap();
+        let masked = GridConfig {
+            mask: Some(mask),
+            ..config
+        };
+        let grid = Grid::try_from_image_with_config(&img, masked).unwrap();
+        assert_eq!(grid, expected);
+
+        // Coordinates stay in the space of the whole image
+        assert_eq!(grid.rows[0].y, 0);
+        assert_eq!(grid.rows[0].kind, LineKind::Empty);
+        assert!(grid.rows[0].height >= 20);
+        assert_eq!(
+            grid.rows.iter().map(|row| row.height).sum::<u32>(),
+            img.height()
+        );
+        assert_eq!(
+            grid.columns.iter().map(|col| col.width).sum::<u32>(),
+            img.width()
+        );
+    }
+
+    #[test_case(GridConfig::default() ; "default")]
+    #[test_case(GridConfig { binarizer: Binarizer::Global(128), ..Default::default() } ; "global")]
+    #[test_case(GridConfig { alpha_cutoff: Some(1), ..Default::default() } ; "alpha")]
+    fn test_mask_in_every_detection_path(config: GridConfig) {
+        use imageproc::{drawing::draw_filled_rect_mut, rect::Rect};
+
+        let (img, mask) = create_masked_layout();
+        let unmasked = Grid::try_from_image_with_config(&img, config.clone()).unwrap();
+        let config = GridConfig {
+            mask: Some(mask),
+            ..config
+        };
+        let mut detector = GridDetector::new(config.clone());
+        let expected = detector.detect(&img).unwrap();
+        assert_ne!(expected, unmasked);
+
+        let view = Grid::try_from_view_with_config(img.as_rgba8().unwrap(), config.clone());
+        assert_eq!(view.unwrap(), expected);
+
+        let pyramid = GridConfig {
+            pyramid: Some(PyramidConfig::default()),
+            ..config.clone()
+        };
+        assert_eq!(
+            Grid::try_from_image_with_config(&img, pyramid).unwrap(),
+            expected
+        );
+
+        let bands = Grid::from_bands(
+            img.width(),
+            img.height(),
+            img.color(),
+            300_000,
+            config.clone(),
+            |rows| Ok(img.crop_imm(0, rows.start, img.width(), rows.len() as u32)),
+        );
+        assert_eq!(bands.unwrap(), expected);
+
+        let mut stream = GridStream::new(img.width(), config.clone()).unwrap();
+        for y in (0..img.height()).step_by(7) {
+            let rows = img.crop_imm(0, y, img.width(), 7.min(img.height() - y));
+            stream.push_rows(&rows).unwrap();
+        }
+        assert_eq!(stream.finish().unwrap(), expected);
+
+        // Ink drawn across masked and unmasked pixels
+        let mut profiles = Grid::try_profiles_from_image_with_config(&img, config).unwrap();
+        let mut frame = img.clone();
+        let dirty = Rect::at(140, 60).of_size(60, 70);
+        draw_filled_rect_mut(&mut frame, dirty, Rgba([0, 0, 0, 255]));
+        let updated = detector
+            .update(&frame, &expected, &mut profiles, dirty)
+            .unwrap();
+        assert_eq!(updated, detector.detect(&frame).unwrap());
+    }
+
+    #[test]
+    fn test_mask_image_must_cover_image() {
+        use std::sync::Arc;
+
+        let img = create_test_image(30, 50, "dense");
+        let config = GridConfig {
+            mask: Some(RegionMask {
+                image: Some(Arc::new(GrayImage::new(30, 49))),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert!(matches!(
+            Grid::try_from_image_with_config(&img, config),
+            Err(GridError::InvalidMask {
+                width: 30,
+                height: 49
+            })
+        ));
+    }
+
+    #[test]
+    fn test_view_alpha_cutoff() {
+        // 16-bit sprites separated by transparent gutters
+        let img = ImageBuffer::from_fn(20, 20, |x, _| {
+            let alpha = if (8..12).contains(&x) { 0 } else { u16::MAX };
+            Rgba([0u16, 0, 0, alpha])
+        });
+        let config = GridConfig {
+            alpha_cutoff: Some(1),
+            merge_threshold_ratio: 0.1,
+            ..Default::default()
+        };
+
+        let grid = Grid::try_from_view_with_config(&img, config.clone()).unwrap();
+
+        assert_eq!(grid.columns.len(), 3);
+        assert_eq!(grid.columns[1].kind, LineKind::Empty);
+        assert_eq!(
+            grid,
+            Grid::try_from_image_with_config(&DynamicImage::ImageRgba16(img), config).unwrap()
+        );
+    }
+
+    #[test_case(true)]
+    #[test_case(false)]
+    fn test_separators_define_cells(enable_parallel: bool) {
+        let img = create_bordered_table();
+        let config = GridConfig {
+            enable_parallel,
+            separators: Some(SeparatorConfig::default()),
+            ..Default::default()
+        };
+
+        let grid = Grid::try_from_image_with_config(&img, config).unwrap();
+
+        assert_eq!(grid.count_rows_by_kind(LineKind::Separator), 3);
+        assert_eq!(grid.count_columns_by_kind(LineKind::Separator), 3);
+        assert_eq!(grid.count_rows_by_kind(LineKind::Full), 2);
+        assert_eq!(grid.count_columns_by_kind(LineKind::Full), 2);
+        assert_yaml_snapshot!("separators_define_cells", grid);
+    }
+
+    #[test]
+    fn test_without_separators_rules_are_swallowed() {
+        let img = create_bordered_table();
+        let grid = Grid::try_from_image_with_config(&img, GridConfig::default()).unwrap();
+
+        assert_eq!(grid.count_rows_by_kind(LineKind::Separator), 0);
+        assert!(grid.rows.iter().all(|row| row.height > 1));
+    }
+
+    #[test]
+    fn test_thick_solid_runs_are_not_separators() {
+        // A 6px solid bar is content, not a ruling line
+        let img = GrayImage::from_fn(20, 20, |_x, y| {
+            if (7..13).contains(&y) {
+                Luma([0u8])
+            } else {
+                Luma([255u8])
+            }
+        });
+        let config = GridConfig {
+            binarizer: Binarizer::Otsu,
+            separators: Some(SeparatorConfig::default()),
+            ..Default::default()
+        };
+
+        let grid =
+            Grid::try_from_image_with_config(&DynamicImage::ImageLuma8(img), config).unwrap();
+        assert_eq!(grid.count_rows_by_kind(LineKind::Separator), 0);
+        assert_eq!(grid.count_rows_by_kind(LineKind::Full), 1);
+    }
+
+    #[test]
+    fn test_merge_small_lines_keeps_separators() {
+        let lines = vec![
+            LineInfo::new(0, 10, LineKind::Full),
+            LineInfo::new(10, 1, LineKind::Separator),
+            LineInfo::new(11, 2, LineKind::Empty),
+            LineInfo::new(13, 10, LineKind::Full),
+        ];
+
+        let merged = Grid::merge_small_lines(lines, 5);
+
+        assert_eq!(
+            merged,
+            SmallVecLine::from_vec(vec![
+                LineInfo::new(0, 10, LineKind::Full),
+                LineInfo::new(10, 1, LineKind::Separator),
+                LineInfo::new(11, 12, LineKind::Empty),
+            ])
+        );
+    }
+
+    #[test_case(MergePolicy::KeepFirst, &[LineInfo::new(0, 22, LineKind::Empty), LineInfo::new(22, 20, LineKind::Empty)])]
+    #[test_case(MergePolicy::MajorityByPixels, &[LineInfo::new(0, 22, LineKind::Full), LineInfo::new(22, 20, LineKind::Empty)])]
+    #[test_case(MergePolicy::AnyFullWins, &[LineInfo::new(0, 22, LineKind::Full), LineInfo::new(22, 20, LineKind::Empty)])]
+    #[test_case(MergePolicy::SameKindOnly, &[LineInfo::new(0, 2, LineKind::Empty), LineInfo::new(2, 20, LineKind::Full), LineInfo::new(22, 20, LineKind::Empty)])]
+    fn test_merge_policy(policy: MergePolicy, expected: &[LineInfo]) {
+        // A tiny leading gutter before a large content block
+        let lines = vec![
+            LineInfo::new(0, 2, LineKind::Empty),
+            LineInfo::new(2, 20, LineKind::Full),
+            LineInfo::new(22, 20, LineKind::Empty),
+        ];
+
+        let merged =
+            Grid::merge_small_lines_with_policy(lines, 5, policy, MergeDirection::Previous);
+
+        assert_eq!(merged.as_slice(), expected);
+    }
+
+    #[test_case(MergeThreshold::Uniform(LineStatistic::Mean), 1)]
+    #[test_case(MergeThreshold::Uniform(LineStatistic::Median), 4)]
+    #[test_case(MergeThreshold::PerKind { empty: LineStatistic::Absolute(2), content: LineStatistic::Median }, 6)]
+    fn test_merge_threshold_ignores_margin(merge_threshold: MergeThreshold, expected_rows: usize) {
+        // A huge top margin followed by three content rows split by thin gutters
+        let img = GrayImage::new(1, 236);
+        let config = GridConfig {
+            merge_threshold,
+            ..Default::default()
+        };
+
+        let rows: SmallVecLine<Row> =
+            Grid::process_dimension_with_config(&img, 236, 1, &config, |_, y, _| match y {
+                0..200 | 210..213 | 223..226 => LineKind::Empty,
+                _ => LineKind::Full,
+            })
+            .unwrap();
+
+        assert_eq!(rows.len(), expected_rows);
+        assert_eq!(rows.iter().map(|row| row.height).sum::<u32>(), 236);
+    }
+
+    #[test_case(0, 0, 0, &[10, 1, 9, 10, 1, 9, 10])]
+    #[test_case(2, 0, 0, &[20, 10, 1, 9, 10])]
+    #[test_case(0, 2, 0, &[10, 1, 9, 20, 10])]
+    #[test_case(0, 0, 2, &[20, 20, 10])]
+    fn test_run_constraints(
+        min_empty_run: u32,
+        min_full_run: u32,
+        hysteresis: u32,
+        expected: &[u32],
+    ) {
+        // Content split by a one pixel gap, and a one pixel speck inside a gutter
+        let img = GrayImage::new(1, 50);
+        let config = GridConfig {
+            min_empty_run,
+            min_full_run,
+            hysteresis,
+            merge_threshold: MergeThreshold::Uniform(LineStatistic::Absolute(0)),
+            ..Default::default()
+        };
+
+        let rows: SmallVecLine<Row> =
+            Grid::process_dimension_with_config(&img, 50, 1, &config, |_, y, _| match y {
+                10 | 20..=29 | 31..=39 => LineKind::Empty,
+                _ => LineKind::Full,
+            })
+            .unwrap();
+
+        let heights: Vec<u32> = rows.iter().map(|row| row.height).collect();
+        assert_eq!(heights, expected);
+    }
+
+    #[test_case(3, 0, &[10, 6, 14])]
+    #[test_case(0, 3, &[10, 6, 14])]
+    #[test_case(7, 0, &[30])]
+    fn test_run_constraints_measure_mixed_content(
+        min_full_run: u32,
+        hysteresis: u32,
+        expected: &[u32],
+    ) {
+        // Full and sparse lines alternating in a single block of content
+        let img = GrayImage::new(1, 30);
+        let config = GridConfig {
+            min_full_run,
+            hysteresis,
+            merge_threshold: MergeThreshold::Uniform(LineStatistic::Absolute(0)),
+            ..Default::default()
+        };
+
+        let rows: SmallVecLine<Row> =
+            Grid::process_dimension_with_config(&img, 30, 1, &config, |_, y, _| match y {
+                10 | 11 | 14 | 15 => LineKind::Full,
+                12 | 13 => LineKind::Sparse,
+                _ => LineKind::Empty,
+            })
+            .unwrap();
+
+        let heights: Vec<u32> = rows.iter().map(|row| row.height).collect();
+        assert_eq!(heights, expected);
+        assert_eq!(rows[0].kind, LineKind::Empty);
+    }
+
+    #[test_case(GridConfig::default())]
+    #[test_case(GridConfig { sparse_ratio: Some(0.3), ..Default::default() })]
+    #[test_case(GridConfig { ink_tolerance: InkTolerance::Ratio(0.02), min_full_run: 3, ..Default::default() })]
+    fn test_profiles_derive_grid_lines(config: GridConfig) {
+        let img = open("tests/large.png").unwrap();
+        let profiles = Grid::try_profiles_from_image_with_config(&img, config.clone()).unwrap();
+        let grid = Grid::try_from_image_with_config(&img, config.clone()).unwrap();
+
+        assert_eq!(profiles.width(), img.width());
+        assert_eq!(profiles.height(), img.height());
+        let rows: SmallVecLine<Row> = profiles
+            .row_lines(&config)
+            .into_iter()
+            .map(Row::new)
+            .collect();
+        let columns: SmallVecLine<Column> = profiles
+            .column_lines(&config)
+            .into_iter()
+            .map(Column::new)
+            .collect();
+        assert_eq!(rows, grid.rows);
+        assert_eq!(columns, grid.columns);
+    }
+
+    #[test]
+    fn test_segment_profile_counts_and_densities_agree() {
+        let counts: Vec<u32> = vec![0, 1, 0, 9, 10, 8, 2, 2, 0, 0, 10, 10];
+        let densities: Vec<f32> = counts.iter().map(|&c| c as f32 / 10.0).collect();
+        let config = GridConfig {
+            ink_tolerance: InkTolerance::Ratio(0.1),
+            sparse_ratio: Some(0.5),
+            merge_threshold_ratio: 0.0,
+            ..Default::default()
+        };
+
+        let from_counts = Grid::segment_profile(&counts, 10, &config);
+        let from_densities = Grid::segment_profile(&densities, 1.0, &config);
+
+        assert_eq!(from_counts, from_densities);
+        assert_eq!(
+            from_counts,
+            SmallVecLine::from_vec(vec![
+                LineInfo::new(0, 3, LineKind::Empty),
+                LineInfo::new(3, 3, LineKind::Full),
+                LineInfo::new(6, 2, LineKind::Sparse),
+                LineInfo::new(8, 2, LineKind::Empty),
+                LineInfo::new(10, 2, LineKind::Full),
+            ])
+        );
+        assert!(Grid::segment_profile::<u32>(&[], 10, &config).is_empty());
+    }
+
+    #[test]
+    fn test_merge_into_smaller_neighbor() {
+        let lines = vec![
+            LineInfo::new(0, 20, LineKind::Full),
+            LineInfo::new(20, 2, LineKind::Empty),
+            LineInfo::new(22, 5, LineKind::Full),
+            LineInfo::new(27, 30, LineKind::Empty),
+        ];
+
+        let merged = Grid::merge_small_lines_with_policy(
+            lines,
+            4,
+            MergePolicy::MajorityByPixels,
+            MergeDirection::SmallerNeighbor,
+        );
+
+        assert_eq!(
+            merged,
+            SmallVecLine::from_vec(vec![
+                LineInfo::new(0, 20, LineKind::Full),
+                LineInfo::new(20, 7, LineKind::Full),
+                LineInfo::new(27, 30, LineKind::Empty),
+            ])
+        );
+    }
+
+    #[test_case(true)]
+    #[test_case(false)]
+    fn test_sparse_lines(enable_parallel: bool) {
+        // Dense content, a gutter, then a few isolated marks
+        let img = GrayImage::from_fn(20, 30, |x, y| {
+            let dense = y < 10 && !(x + y).is_multiple_of(2);
+            let sparse = y >= 20 && x == y % 20;
+            if dense || sparse {
+                Luma([0u8])
+            } else {
+                Luma([255u8])
+            }
+        });
+        let config = GridConfig {
+            enable_parallel,
+            merge_threshold_ratio: 0.1,
+            sparse_ratio: Some(0.2),
+            ..Default::default()
+        };
+
+        let grid =
+            Grid::try_from_image_with_config(&DynamicImage::ImageLuma8(img), config).unwrap();
+
+        assert_eq!(
+            grid.rows,
+            SmallVecLine::from_vec(vec![
+                make_line!(Row, (0, 10, LineKind::Full)),
+                make_line!(Row, (10, 10)),
+                make_line!(Row, (20, 10, LineKind::Sparse)),
+            ])
+        );
+        assert_eq!(grid.count_rows_by_kind(LineKind::Sparse), 1);
+        assert_eq!(grid.filtered_rows(|row| row.kind.has_content()).count(), 2);
+    }
+
+    #[test]
+    fn test_draw_lines_by_kind() {
+        let mut image = RgbaImage::new(10, 10);
+        let grid = make_grid! {
+            rows: [
+                (0, 4, LineKind::Separator),
+            ],
+            columns: []
+        };
+        let config = GridDrawingConfig {
+            kind_color_provider: Some(Box::new(kind_color)),
+            ..Default::default()
+        };
+
+        grid.draw(&mut image, &config).unwrap();
+
+        assert_eq!(*image.get_pixel(5, 4), kind_color(&LineKind::Separator));
+    }
+
+    #[test]
+    fn test_layout_tree_two_columns() {
+        // A two-column page: the left column holds two blocks, the right one a single block
+        let img = GrayImage::from_fn(60, 40, |x, y| {
+            let in_left = x < 25 && !(10..20).contains(&y);
+            let in_right = x >= 35;
+            if (in_left || in_right) && !(x + y).is_multiple_of(2) {
+                Luma([0u8])
+            } else {
+                Luma([255u8])
+            }
+        });
+        let dynamic_img = DynamicImage::ImageLuma8(img);
+        let config = GridConfig {
+            merge_threshold_ratio: 0.1,
+            ..Default::default()
+        };
+
+        let tree = Grid::try_tree_from_image_with_config(&dynamic_img, config).unwrap();
+
+        assert_eq!(tree.depth(), 2);
+        assert_eq!(tree.children.len(), 2);
+        let left = &tree.children[0];
+        assert_eq!((left.x, left.y, left.width, left.height), (0, 0, 25, 40));
+        assert_eq!(left.grid.count_rows_by_kind(LineKind::Full), 2);
+        assert_eq!(left.children[1].y, 20);
+        let right = &tree.children[1];
+        assert_eq!(right.grid.columns[0].x, 35);
+        assert!(right.is_leaf());
+        #[cfg(feature = "serde")]
+        assert_yaml_snapshot!("layout_tree_two_columns", tree);
+    }
+
+    #[test]
+    fn test_layout_tree_respects_max_depth() {
+        let img = create_test_image(20, 20, "checkerboard");
+        let config = GridConfig {
+            max_depth: 0,
+            ..Default::default()
+        };
+
+        let tree = Grid::try_tree_from_image_with_config(&img, config.clone()).unwrap();
+        assert!(tree.is_leaf());
+        assert_eq!(
+            tree.grid,
+            Grid::try_from_image_with_config(&img, config).unwrap()
+        );
+    }
+
     /// Creates a test image with specified dimensions and pattern
     ///
     /// # Arguments
@@ -1076,6 +2671,7 @@ mod tests {
                     threshold_block_size: block_size,
                     merge_threshold_ratio: ratio,
                     enable_parallel: parallel,
+                    ..Default::default()
                 };
                 let img = create_test_image(100, 100, "checkerboard");
                 let result = Grid::try_from_image_with_config(&img, config).is_ok();
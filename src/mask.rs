@@ -0,0 +1,133 @@
+use crate::detector::clip;
+use crate::GridError;
+use image::GrayImage;
+use imageproc::rect::Rect;
+use std::ops::Range;
+use std::sync::Arc;
+use tracing::*;
+
+/// Regions of an image left out of detection, such as the chrome around the
+/// content pane of a screenshot, a watermark or a cursor.
+///
+/// Masked pixels are empty whatever their color. They are cleared after
+/// binarization, so the thresholds of the binarizer and the background
+/// detection still see the whole image, and the grid keeps the coordinates of
+/// the whole image: the rows and columns outside the region of interest are
+/// [`crate::LineKind::Empty`].
+///
+/// # Example
+/// ```
+/// use grider::RegionMask;
+/// use imageproc::rect::Rect;
+///
+/// let mask = RegionMask {
+///     roi: Some(Rect::at(0, 40).of_size(800, 560)),
+///     exclusions: vec![Rect::at(700, 540).of_size(100, 60)],
+///     ..Default::default()
+/// };
+/// assert!(mask.is_masked(10, 10)); // Outside the region of interest
+/// assert!(mask.is_masked(750, 550)); // Excluded
+/// assert!(!mask.is_masked(10, 50));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RegionMask {
+    /// Only pixels inside this rectangle are detected (default: the whole image)
+    pub roi: Option<Rect>,
+    /// Pixels inside these rectangles are empty (default: none)
+    pub exclusions: Vec<Rect>,
+    /// Pixels that are black (0) in this image are empty; it must cover the
+    /// image from its top-left corner (default: none)
+    pub image: Option<Arc<GrayImage>>,
+}
+
+impl RegionMask {
+    /// Returns `true` if the pixel at (`x`, `y`) is masked.
+    pub fn is_masked(&self, x: u32, y: u32) -> bool {
+        let contains = |rect: &Rect| {
+            let (x, y) = (x as i64, y as i64);
+            let (left, top) = (rect.left() as i64, rect.top() as i64);
+            (left..left + rect.width() as i64).contains(&x)
+                && (top..top + rect.height() as i64).contains(&y)
+        };
+        self.roi.is_some_and(|roi| !contains(&roi))
+            || self.exclusions.iter().any(contains)
+            || self
+                .image
+                .as_ref()
+                .and_then(|image| image.get_pixel_checked(x, y))
+                .is_some_and(|pixel| pixel.0[0] == 0)
+    }
+
+    /// Clears the masked pixels of a binarized buffer whose top-left pixel is
+    /// at `origin` in the image.
+    ///
+    /// # Errors
+    /// [`GridError::InvalidMask`] if the mask image does not cover the buffer.
+    pub(crate) fn apply(
+        &self,
+        binarized: &mut GrayImage,
+        origin: (u32, u32),
+    ) -> Result<(), GridError> {
+        let (width, height) = binarized.dimensions();
+        let (x, y) = origin;
+        if let Some(image) = &self.image {
+            if x + width > image.width() || y + height > image.height() {
+                error!(
+                    "Mask of {}x{} does not cover {}x{} pixels at ({}, {})",
+                    image.width(),
+                    image.height(),
+                    width,
+                    height,
+                    x,
+                    y
+                );
+                return Err(GridError::InvalidMask {
+                    width: image.width(),
+                    height: image.height(),
+                });
+            }
+        }
+        let _span = debug_span!("mask").entered();
+
+        // Rows and columns of the buffer covered by each rectangle
+        let local = |rect: &Rect| -> (Range<u32>, Range<u32>) {
+            (
+                clip(rect.top().saturating_sub(y as i32), rect.height(), height),
+                clip(rect.left().saturating_sub(x as i32), rect.width(), width),
+            )
+        };
+        let roi = self.roi.as_ref().map(local);
+        let exclusions: Vec<_> = self
+            .exclusions
+            .iter()
+            .map(local)
+            .filter(|(rows, columns)| !rows.is_empty() && !columns.is_empty())
+            .collect();
+
+        for (row, pixels) in (0..).zip(binarized.chunks_exact_mut(width as usize)) {
+            if let Some((rows, columns)) = &roi {
+                if !rows.contains(&row) {
+                    pixels.fill(255);
+                    continue;
+                }
+                pixels[..columns.start as usize].fill(255);
+                pixels[columns.end as usize..].fill(255);
+            }
+            for (rows, columns) in &exclusions {
+                if rows.contains(&row) {
+                    pixels[columns.start as usize..columns.end as usize].fill(255);
+                }
+            }
+            if let Some(image) = &self.image {
+                let offset = (y + row) as usize * image.width() as usize + x as usize;
+                let keep = &image.as_raw()[offset..offset + width as usize];
+                for (pixel, &keep) in pixels.iter_mut().zip(keep) {
+                    if keep == 0 {
+                        *pixel = 255;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
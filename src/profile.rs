@@ -0,0 +1,266 @@
+use crate::{
+    Grid, GridConfig, GridDetector, GridError, InkTolerance, LineInfo, LineKind, SmallVecLine,
+};
+use image::{DynamicImage, GrayImage};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+use tracing::*;
+
+/// Horizontal and vertical projection profiles of a binarized image.
+///
+/// Each profile holds the number of ink (non-white) pixels of every line. The
+/// runs of [`LineInfo`] detected by [`Grid`] can be derived from them with
+/// [`ProjectionProfiles::row_lines`] and [`ProjectionProfiles::column_lines`].
+#[derive(Debug, PartialEq, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ProjectionProfiles {
+    /// Number of ink pixels in each row, from top to bottom.
+    pub rows: Vec<u32>,
+    /// Number of ink pixels in each column, from left to right.
+    pub columns: Vec<u32>,
+}
+
+impl ProjectionProfiles {
+    /// Computes the projection profiles of a binarized image.
+    ///
+    /// Both profiles are accumulated in a single row-major pass over the raw
+    /// buffer, so that column counts do not stride across memory.
+    ///
+    /// # Example
+    /// ```
+    /// use grider::profile::ProjectionProfiles;
+    /// use image::{GrayImage, Luma};
+    ///
+    /// let mut img = GrayImage::from_pixel(3, 2, Luma([255u8]));
+    /// img.put_pixel(0, 1, Luma([0u8]));
+    /// img.put_pixel(2, 1, Luma([0u8]));
+    ///
+    /// let profiles = ProjectionProfiles::from_binarized(&img);
+    /// assert_eq!(profiles.rows, vec![0, 2]);
+    /// assert_eq!(profiles.columns, vec![1, 0, 1]);
+    /// ```
+    pub fn from_binarized(binarized_img: &GrayImage) -> Self {
+        let mut profiles = ProjectionProfiles::default();
+        profiles.fill(binarized_img);
+        profiles
+    }
+
+    /// Recomputes the profiles of a binarized image, reusing their allocations.
+    pub(crate) fn fill(&mut self, binarized_img: &GrayImage) {
+        let (width, height) = binarized_img.dimensions();
+        self.reset(width, height);
+        if width == 0 {
+            return;
+        }
+        count_band(
+            &mut self.rows,
+            &mut self.columns,
+            binarized_img.as_raw(),
+            width as usize,
+        );
+    }
+
+    /// Recomputes the profiles of a binarized image in parallel.
+    ///
+    /// The image is split into bands of `band_len` rows. Each band counts its own
+    /// rows and partial column totals, which are then summed, so the result is
+    /// identical to [`ProjectionProfiles::fill`].
+    #[cfg(feature = "rayon")]
+    pub(crate) fn fill_parallel(&mut self, binarized_img: &GrayImage, band_len: u32) {
+        let (width, height) = binarized_img.dimensions();
+        self.reset(width, height);
+        if width == 0 {
+            return;
+        }
+        let width = width as usize;
+        let band_len = band_len.max(1) as usize;
+
+        let columns = self
+            .rows
+            .par_chunks_mut(band_len)
+            .zip(binarized_img.as_raw().par_chunks(width * band_len))
+            .map(|(rows, pixels)| {
+                let mut columns = vec![0; width];
+                count_band(rows, &mut columns, pixels, width);
+                columns
+            })
+            .reduce_with(|mut total, band| {
+                for (total, ink) in total.iter_mut().zip(band) {
+                    *total += ink;
+                }
+                total
+            });
+        if let Some(columns) = columns {
+            self.columns = columns;
+        }
+    }
+
+    /// Counts the ink of binarized rows starting at row `y`, setting their row
+    /// profile and adding to the column profile.
+    pub(crate) fn add_rows(&mut self, y: u32, pixels: &[u8]) {
+        let width = self.columns.len();
+        let rows = &mut self.rows[y as usize..y as usize + pixels.len() / width];
+        count_band(rows, &mut self.columns, pixels, width);
+    }
+
+    /// Resizes both profiles to the image dimensions and zeroes them.
+    fn reset(&mut self, width: u32, height: u32) {
+        self.rows.clear();
+        self.rows.resize(height as usize, 0);
+        self.columns.clear();
+        self.columns.resize(width as usize, 0);
+    }
+
+    /// Returns the width of the profiled image.
+    pub fn width(&self) -> u32 {
+        self.columns.len() as u32
+    }
+
+    /// Returns the height of the profiled image.
+    pub fn height(&self) -> u32 {
+        self.rows.len() as u32
+    }
+
+    /// Derives the merged row runs from the row profile.
+    ///
+    /// Lines are classified and merged as in [`Grid::try_from_image_with_config`],
+    /// except that ruled-line separators are not detected.
+    pub fn row_lines(&self, config: &GridConfig) -> SmallVecLine<LineInfo> {
+        Self::lines(&self.rows, self.width(), config)
+    }
+
+    /// Derives the merged column runs from the column profile.
+    ///
+    /// See [`ProjectionProfiles::row_lines`] for details.
+    pub fn column_lines(&self, config: &GridConfig) -> SmallVecLine<LineInfo> {
+        Self::lines(&self.columns, self.height(), config)
+    }
+
+    /// Classifies, collects and merges the lines of a profile.
+    fn lines(profile: &[u32], secondary_dim: u32, config: &GridConfig) -> SmallVecLine<LineInfo> {
+        Grid::segment_profile(profile, secondary_dim, config)
+    }
+}
+
+/// A value of a 1-D profile that can be classified into a [`LineKind`].
+///
+/// `scale` is the largest value a line can take: the line length for ink
+/// counts, or `1.0` for normalized densities. [`GridConfig::ink_tolerance`] and
+/// [`GridConfig::sparse_ratio`] are interpreted relative to it.
+pub trait ProfileValue: Copy {
+    /// Classifies the value of one line of the profile.
+    fn classify(self, scale: Self, config: &GridConfig) -> LineKind;
+}
+
+impl ProfileValue for u32 {
+    fn classify(self, scale: u32, config: &GridConfig) -> LineKind {
+        Grid::classify_ink_count(self, scale, config)
+    }
+}
+
+impl ProfileValue for f32 {
+    fn classify(self, scale: f32, config: &GridConfig) -> LineKind {
+        let max_value = match config.ink_tolerance {
+            InkTolerance::Pixels(pixels) => (pixels as f32).min(scale),
+            InkTolerance::Ratio(ratio) => scale * ratio.clamp(0.0, 1.0),
+        };
+        if self <= max_value {
+            LineKind::Empty
+        } else if config
+            .sparse_ratio
+            .is_some_and(|sparse_ratio| self < sparse_ratio * scale)
+        {
+            LineKind::Sparse
+        } else {
+            LineKind::Full
+        }
+    }
+}
+
+impl Grid {
+    /// Segments an arbitrary 1-D profile into runs of lines.
+    ///
+    /// Every value is classified relative to `scale` (see [`ProfileValue`]), then
+    /// runs are collected and small runs merged with the same configuration as
+    /// the rows and columns of an image. Image detection is a thin wrapper that
+    /// segments the ink profiles of the binarized image.
+    ///
+    /// # Arguments
+    /// * `profile` - One value per line, such as ink counts or densities.
+    /// * `scale` - The largest value a line can take.
+    /// * `config` - The classification and merge configuration.
+    ///
+    /// # Returns
+    /// The merged runs, or no runs for an empty profile.
+    ///
+    /// # Example
+    /// ```
+    /// use grider::{Grid, GridConfig, InkTolerance, LineInfo, LineKind};
+    ///
+    /// // Audio envelope: silence is anything below 5% of the peak amplitude
+    /// let envelope = [0.01, 0.02, 0.8, 0.9, 0.7, 0.6, 0.0, 0.01, 0.03, 0.02];
+    /// let config = GridConfig {
+    ///     ink_tolerance: InkTolerance::Ratio(0.05),
+    ///     merge_threshold_ratio: 0.0,
+    ///     ..Default::default()
+    /// };
+    /// let runs = Grid::segment_profile(&envelope, 1.0, &config);
+    /// assert_eq!(
+    ///     runs.as_slice(),
+    ///     &[
+    ///         LineInfo::new(0, 2, LineKind::Empty),
+    ///         LineInfo::new(2, 4, LineKind::Full),
+    ///         LineInfo::new(6, 4, LineKind::Empty),
+    ///     ]
+    /// );
+    /// ```
+    pub fn segment_profile<V: ProfileValue>(
+        profile: &[V],
+        scale: V,
+        config: &GridConfig,
+    ) -> SmallVecLine<LineInfo> {
+        trace!("Segmenting profile of {} lines", profile.len());
+        Self::segment_classified(profile.len() as u32, config, |i| {
+            profile[i as usize].classify(scale, config)
+        })
+    }
+
+    /// Computes the projection profiles of an image, binarized as in
+    /// [`Grid::try_from_image_with_config`].
+    ///
+    /// # Example
+    /// ```
+    /// use grider::{Grid, GridConfig};
+    /// use image::open;
+    ///
+    /// let img = open("tests/large.png").unwrap();
+    /// let config = GridConfig::default();
+    /// let profiles = Grid::try_profiles_from_image_with_config(&img, config.clone()).unwrap();
+    ///
+    /// let rows = profiles.row_lines(&config);
+    /// let grid = Grid::try_from_image_with_config(&img, config).unwrap();
+    /// assert_eq!(rows.len(), grid.rows.len());
+    /// ```
+    pub fn try_profiles_from_image_with_config(
+        image: &DynamicImage,
+        config: GridConfig,
+    ) -> Result<ProjectionProfiles, GridError> {
+        trace!("Computing projection profiles with config: {:?}", config);
+        let mut detector = GridDetector::new(config);
+        detector.binarize(image)?;
+        Ok(ProjectionProfiles::from_binarized(detector.binarized()))
+    }
+}
+
+/// Counts the ink of a band of rows, setting `rows` and adding to `columns`.
+fn count_band(rows: &mut [u32], columns: &mut [u32], pixels: &[u8], width: usize) {
+    for (row_ink, pixels) in rows.iter_mut().zip(pixels.chunks_exact(width)) {
+        let mut ink = 0;
+        for (column_ink, &pixel) in columns.iter_mut().zip(pixels) {
+            let is_ink = (pixel != 255) as u32;
+            *column_ink += is_ink;
+            ink += is_ink;
+        }
+        *row_ink = ink;
+    }
+}
@@ -0,0 +1,417 @@
+use crate::profile::{ProfileValue, ProjectionProfiles};
+use crate::{
+    Binarizer, Column, Grid, GridConfig, GridDetector, GridError, LineInfo, LineKind, LineTrait,
+    RegionMask, Row,
+};
+use image::{DynamicImage, GenericImageView, GrayImage};
+use imageproc::rect::Rect;
+use std::ops::Range;
+use std::sync::Arc;
+use tracing::*;
+
+const DEFAULT_PYRAMID_SCALE: u32 = 4;
+const DEFAULT_REFINE_WINDOW: u32 = 8;
+
+/// Configuration for coarse-to-fine detection.
+///
+/// Rows and columns are first detected on a copy of the image downscaled by
+/// `scale`, in which every block of pixels holding ink stays ink. Each boundary
+/// between two runs is then moved to its exact position by classifying the
+/// full-resolution lines within `refine_window` pixels of it, and the runs are
+/// merged at full resolution. Only the lines around boundaries are binarized
+/// at full resolution.
+///
+/// Gaps narrower than about twice `scale` pixels can be missed by the coarse
+/// pass, and the kind of the lines away from boundaries is decided on the
+/// downscaled image. Missing a gap changes the line statistics of relative
+/// merge thresholds, so the grid can then differ from full-resolution
+/// detection, although every boundary it keeps is exact.
+///
+/// With [`crate::Binarizer::Global`], [`GridConfig::alpha_cutoff`] or
+/// [`GridConfig::color_key`], a downscaled block holds ink if any of its pixels
+/// does. The grid is then the same as at full resolution if every run of empty
+/// lines is at least twice `scale` long, `refine_window` is at least `scale`
+/// and [`GridConfig::ink_tolerance`] and [`GridConfig::sparse_ratio`] are left
+/// at their defaults. Local binarizers see the average of each block, so their
+/// coarse ink can differ further.
+///
+/// # Performance
+/// The pyramid only pays off with local binarizers such as the default
+/// [`crate::Binarizer::Adaptive`], whose cost per pixel is high. A global
+/// level, an alpha cutoff or a color key is cheaper to apply to the whole
+/// image than to downscale it, so detection at full resolution is faster for
+/// them.
+///
+/// # Entry points
+/// Only [`GridDetector::detect`] and [`Grid::try_from_image_with_config`] use
+/// the pyramid. Views, raw buffers, bands, streams, updates, profiles and
+/// layout trees ([`GridDetector::detect_view`], [`Grid::from_raw`],
+/// [`Grid::from_bands`], [`crate::GridStream`], [`GridDetector::update`],
+/// [`Grid::try_profiles_from_image_with_config`] and
+/// [`Grid::try_tree_from_image_with_config`]) always detect at full
+/// resolution and ignore it.
+///
+/// Separators, and unless the alpha channel or a color key decides the ink,
+/// background detection and [`crate::Binarizer::Otsu`] need the whole image:
+/// the pyramid then fails with [`GridError::UnsupportedConfig`]. Masks are
+/// downscaled along with the image.
+///
+/// # Example
+/// ```
+/// use grider::{Grid, GridConfig, PyramidConfig};
+/// use image::open;
+///
+/// let img = open("tests/large.png").unwrap();
+/// let config = GridConfig {
+///     pyramid: Some(PyramidConfig::default()),
+///     ..Default::default()
+/// };
+/// let grid = Grid::try_from_image_with_config(&img, config).unwrap();
+/// assert_eq!(grid.rows.iter().map(|row| row.height).sum::<u32>(), img.height());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PyramidConfig {
+    /// Downscaling factor of the coarse image; 0 and 1 disable the coarse
+    /// pass (default: 4)
+    pub scale: u32,
+    /// Number of full-resolution lines searched on each side of a coarse
+    /// boundary (default: 8)
+    pub refine_window: u32,
+}
+
+impl Default for PyramidConfig {
+    fn default() -> Self {
+        PyramidConfig {
+            scale: DEFAULT_PYRAMID_SCALE,
+            refine_window: DEFAULT_REFINE_WINDOW,
+        }
+    }
+}
+
+impl GridDetector {
+    /// Detects the grid of an image on a downscaled copy, then refines every
+    /// boundary at full resolution.
+    pub(crate) fn detect_coarse_to_fine(
+        &mut self,
+        image: &DynamicImage,
+        pyramid: PyramidConfig,
+    ) -> Result<Grid, GridError> {
+        let _span = debug_span!("detect_grid_pyramid").entered();
+        let (width, height) = image.dimensions();
+        let overlap = self.local_overlap(image.color())?;
+        let config = self.config().clone();
+        debug!(
+            "Detecting at 1/{} scale with a refine window of {}",
+            pyramid.scale, pyramid.refine_window
+        );
+
+        // Collect raw runs on the downscaled image
+        let (coarse, coarse_config) = self.downscale(image, pyramid.scale)?;
+        let mut coarse_detector = GridDetector::new(coarse_config);
+        coarse_detector.binarize(&DynamicImage::ImageLuma8(coarse))?;
+        let profiles = ProjectionProfiles::from_binarized(coarse_detector.binarized());
+        let coarse_config = coarse_detector.config();
+        let row_runs = Grid::collect_runs(profiles.height(), |y| {
+            profiles.rows[y as usize].classify(profiles.width(), coarse_config)
+        });
+        let column_runs = Grid::collect_runs(profiles.width(), |x| {
+            profiles.columns[x as usize].classify(profiles.height(), coarse_config)
+        });
+
+        // Move every boundary to its full-resolution position, then merge
+        let rows = debug_span!("row_pass").in_scope(|| {
+            let runs = self.refine_runs(&row_runs, &pyramid, height, width, |detector, rows| {
+                detector.rows_ink(image, rows, overlap)
+            })?;
+            Ok::<_, GridError>(Grid::segment_runs(runs, &config))
+        })?;
+        let columns = debug_span!("column_pass").in_scope(|| {
+            let runs = self.refine_runs(
+                &column_runs,
+                &pyramid,
+                width,
+                height,
+                |detector, columns| detector.columns_ink(image, columns, overlap),
+            )?;
+            Ok::<_, GridError>(Grid::segment_runs(runs, &config))
+        })?;
+
+        Ok(Grid {
+            rows: rows.into_iter().map(Row::new).collect(),
+            columns: columns.into_iter().map(Column::new).collect(),
+        })
+    }
+
+    /// Downscales the image by `scale`, keeping a block as ink if any of its
+    /// pixels is, and returns it with the configuration to binarize it.
+    fn downscale(
+        &mut self,
+        image: &DynamicImage,
+        scale: u32,
+    ) -> Result<(GrayImage, GridConfig), GridError> {
+        let _span = debug_span!("downscale").entered();
+        let mut config = GridConfig {
+            pyramid: None,
+            color_key: None,
+            alpha_cutoff: None,
+            mask: self
+                .config()
+                .mask
+                .as_ref()
+                .map(|mask| downscale_mask(mask, scale)),
+            ..self.config().clone()
+        };
+
+        if !self.uses_luma(image.color()) {
+            // Masks are binary: pool them and keep them as they are
+            self.binarize(image)?;
+            config.binarizer = Binarizer::Global(128);
+            config.invert = false;
+            return Ok((pool(self.binarized(), scale, Pooling::Darkest), config));
+        }
+
+        // Local binarizers compare pixels to their neighborhood, which averaging
+        // preserves; a global level needs the darkest pixel of each block to
+        // keep thin strokes
+        let pooling = match config.binarizer {
+            Binarizer::Global(_) | Binarizer::Otsu if config.invert => Pooling::Lightest,
+            Binarizer::Global(_) | Binarizer::Otsu => Pooling::Darkest,
+            _ => Pooling::Mean,
+        };
+        config.threshold_block_size = (config.threshold_block_size / scale).max(1);
+        let img = self.luma(image);
+        Ok((pool(img, scale, pooling), config))
+    }
+
+    /// Converts coarse runs to full resolution, moving the boundaries to the
+    /// splits that best agree with the kinds of the full-resolution lines
+    /// around them.
+    ///
+    /// `ink` counts the ink of a range of full-resolution lines.
+    fn refine_runs(
+        &mut self,
+        runs: &[LineInfo],
+        pyramid: &PyramidConfig,
+        primary_dim: u32,
+        secondary_dim: u32,
+        mut ink: impl FnMut(&mut GridDetector, Range<u32>) -> Result<Vec<u32>, GridError>,
+    ) -> Result<Vec<LineInfo>, GridError> {
+        let scale = pyramid.scale;
+        let windows: Vec<Range<u32>> = runs
+            .windows(2)
+            .map(|pair| {
+                let center = pair[1].start * scale;
+                let lo = center
+                    .saturating_sub(pyramid.refine_window)
+                    .max(pair[0].start * scale + 1);
+                let hi = (center + pyramid.refine_window)
+                    .min((pair[1].start + pair[1].length) * scale - 1)
+                    .min(primary_dim - 1);
+                lo.min(center)..hi.max(center)
+            })
+            .collect();
+        trace!("Refining {} boundaries", windows.len());
+
+        let config = self.config().clone();
+        let mut positions = Vec::with_capacity(windows.len());
+        let mut first = 0;
+        while first < windows.len() {
+            // Binarize overlapping windows together
+            let mut span = windows[first].clone();
+            let mut last = first + 1;
+            while last < windows.len() && windows[last].start <= span.end {
+                span.end = span.end.max(windows[last].end);
+                last += 1;
+            }
+            let ink = if span.is_empty() {
+                Vec::new()
+            } else {
+                ink(self, span.clone())?
+            };
+            let kinds: Vec<LineKind> = ink
+                .into_iter()
+                .map(|ink| ink.classify(secondary_dim, &config))
+                .collect();
+
+            let splits = best_splits(
+                &kinds,
+                &windows[first..last],
+                span.start,
+                &runs[first..=last],
+            );
+            positions.extend(splits);
+            first = last;
+        }
+
+        // Rebuild the runs from the refined boundaries, dropping emptied runs
+        let ends = positions.iter().copied().chain([primary_dim]);
+        let mut refined: Vec<LineInfo> = Vec::with_capacity(runs.len());
+        let mut start = 0;
+        for (run, end) in runs.iter().zip(ends) {
+            let end = end.max(start);
+            match refined.last_mut() {
+                _ if end == start => {}
+                Some(last) if last.kind == run.kind => last.length += end - start,
+                _ => refined.push(LineInfo::new(start, end - start, run.kind.clone())),
+            }
+            start = end;
+        }
+        Ok(refined)
+    }
+}
+
+/// Returns the boundaries, one within each of `windows`, that split the lines
+/// of `kinds` from `start` into `runs` misclassifying the fewest lines.
+///
+/// Boundaries are chosen together and strictly increase, so that no run is
+/// left without lines even when the windows around it overlap.
+fn best_splits(
+    kinds: &[LineKind],
+    windows: &[Range<u32>],
+    start: u32,
+    runs: &[LineInfo],
+) -> Vec<u32> {
+    // Number of lines of each kind before every line
+    let mut counts = vec![[0u32; LineKind::COUNT]; kinds.len() + 1];
+    for (i, kind) in kinds.iter().enumerate() {
+        counts[i + 1] = counts[i];
+        counts[i + 1][kind.index()] += 1;
+    }
+    let misses = |kind: &LineKind, lines: Range<u32>| {
+        let (from, to) = ((lines.start - start) as usize, (lines.end - start) as usize);
+        lines.len() as u32 - (counts[to][kind.index()] - counts[from][kind.index()])
+    };
+
+    // Fewest misclassified lines up to each position of a boundary, and the
+    // position of the previous boundary reaching it
+    let mut costs: Vec<Vec<(u32, u32)>> = Vec::with_capacity(windows.len());
+    for (i, window) in windows.iter().enumerate() {
+        let kind = &runs[i].kind;
+        let row = (window.start..=window.end)
+            .map(|position| match i {
+                0 => (misses(kind, start..position), start),
+                _ => (windows[i - 1].start..=windows[i - 1].end)
+                    .zip(&costs[i - 1])
+                    .filter(|&(previous, _)| previous < position)
+                    .map(|(previous, &(cost, _))| {
+                        (
+                            cost.saturating_add(misses(kind, previous..position)),
+                            previous,
+                        )
+                    })
+                    .min_by_key(|&(cost, _)| cost)
+                    .unwrap_or((u32::MAX, start)),
+            })
+            .collect();
+        costs.push(row);
+    }
+
+    // Close the last run at the end of the lines and walk the boundaries back
+    let (Some(window), Some(row)) = (windows.last(), costs.last()) else {
+        return Vec::new();
+    };
+    let end = start + kinds.len() as u32;
+    let kind = &runs[windows.len()].kind;
+    let mut position = (window.start..=window.end)
+        .zip(row)
+        .min_by_key(|&(position, &(cost, _))| {
+            cost.saturating_add(misses(kind, position..end.max(position)))
+        })
+        .map_or(window.start, |(position, _)| position);
+    let mut splits = vec![0; windows.len()];
+    for i in (0..windows.len()).rev() {
+        splits[i] = position;
+        position = costs[i][(position - windows[i].start) as usize].1;
+    }
+    splits
+}
+
+/// Downscales a mask by `scale`, masking a block only if all its pixels are,
+/// so that the coarse pass keeps every boundary of the unmasked pixels.
+fn downscale_mask(mask: &RegionMask, scale: u32) -> RegionMask {
+    RegionMask {
+        roi: mask.roi.and_then(|roi| scale_rect(&roi, scale, true)),
+        exclusions: mask
+            .exclusions
+            .iter()
+            .filter_map(|rect| scale_rect(rect, scale, false))
+            .collect(),
+        image: mask
+            .image
+            .as_ref()
+            .map(|image| Arc::new(pool(image, scale, Pooling::Lightest))),
+    }
+}
+
+/// Downscales a rectangle by `scale`, growing it to the blocks it touches if
+/// `outward` and shrinking it to the blocks it covers otherwise.
+fn scale_rect(rect: &Rect, scale: u32, outward: bool) -> Option<Rect> {
+    let scale = scale as i64;
+    let floor = |value: i64| value.div_euclid(scale);
+    let ceil = |value: i64| -(-value).div_euclid(scale);
+    let (left, top) = (rect.left() as i64, rect.top() as i64);
+    let (right, bottom) = (left + rect.width() as i64, top + rect.height() as i64);
+    let (left, top, right, bottom) = if outward {
+        (floor(left), floor(top), ceil(right), ceil(bottom))
+    } else {
+        (ceil(left), ceil(top), floor(right), floor(bottom))
+    };
+    (right > left && bottom > top).then(|| {
+        Rect::at(left as i32, top as i32).of_size((right - left) as u32, (bottom - top) as u32)
+    })
+}
+
+/// How the pixels of a block are combined into a downscaled pixel.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Pooling {
+    Darkest,
+    Lightest,
+    Mean,
+}
+
+/// Downscales a grayscale image by `scale`, combining the pixels of every
+/// block with `pooling`.
+fn pool(img: &GrayImage, scale: u32, pooling: Pooling) -> GrayImage {
+    let (width, height) = img.dimensions();
+    let scale = scale as usize;
+    let coarse_width = (width as usize).div_ceil(scale);
+    let coarse_height = (height as usize).div_ceil(scale);
+    let initial = match pooling {
+        Pooling::Darkest => u32::MAX,
+        Pooling::Lightest | Pooling::Mean => 0,
+    };
+    let mut blocks = vec![initial; coarse_width];
+    let mut counts = vec![0u32; coarse_width];
+    let mut coarse = Vec::with_capacity(coarse_width * coarse_height);
+
+    for rows in img.as_raw().chunks(width as usize * scale) {
+        blocks.fill(initial);
+        counts.fill(0);
+        for row in rows.chunks_exact(width as usize) {
+            for ((block, count), pixels) in
+                blocks.iter_mut().zip(&mut counts).zip(row.chunks(scale))
+            {
+                for &pixel in pixels {
+                    let pixel = pixel as u32;
+                    *block = match pooling {
+                        Pooling::Darkest => (*block).min(pixel),
+                        Pooling::Lightest => (*block).max(pixel),
+                        Pooling::Mean => *block + pixel,
+                    };
+                }
+                *count += pixels.len() as u32;
+            }
+        }
+        coarse.extend(
+            blocks
+                .iter()
+                .zip(&counts)
+                .map(|(&block, &count)| match pooling {
+                    Pooling::Mean => (block / count.max(1)) as u8,
+                    _ => block as u8,
+                }),
+        );
+    }
+    GrayImage::from_raw(coarse_width as u32, coarse_height as u32, coarse)
+        .expect("downscaled image matches dimensions")
+}
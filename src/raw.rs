@@ -0,0 +1,195 @@
+use crate::{Grid, GridConfig, GridError};
+use image::{GenericImageView, Luma, Pixel, Rgb, Rgba};
+use tracing::*;
+
+/// Pixel layout of a raw frame buffer passed to [`Grid::from_raw`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelFormat {
+    /// 8-bit blue, green, red and alpha channels, as produced by most screen capture APIs.
+    Bgra8,
+    /// 8-bit red, green and blue channels.
+    Rgb8,
+    /// 8-bit red, green, blue and alpha channels.
+    Rgba8,
+    /// 8-bit grayscale.
+    Gray8,
+}
+
+impl PixelFormat {
+    /// Returns the number of bytes per pixel.
+    pub fn bytes_per_pixel(&self) -> usize {
+        match self {
+            PixelFormat::Bgra8 | PixelFormat::Rgba8 => 4,
+            PixelFormat::Rgb8 => 3,
+            PixelFormat::Gray8 => 1,
+        }
+    }
+}
+
+/// Decodes one pixel of a raw layout.
+trait RawLayout {
+    type Pixel: Pixel<Subpixel = u8>;
+    const BYTES_PER_PIXEL: usize;
+    fn decode(bytes: &[u8]) -> Self::Pixel;
+}
+
+struct Bgra8;
+struct Rgb8;
+struct Rgba8;
+struct Gray8;
+
+impl RawLayout for Bgra8 {
+    type Pixel = Rgba<u8>;
+    const BYTES_PER_PIXEL: usize = 4;
+    fn decode(bytes: &[u8]) -> Rgba<u8> {
+        Rgba([bytes[2], bytes[1], bytes[0], bytes[3]])
+    }
+}
+
+impl RawLayout for Rgb8 {
+    type Pixel = Rgb<u8>;
+    const BYTES_PER_PIXEL: usize = 3;
+    fn decode(bytes: &[u8]) -> Rgb<u8> {
+        Rgb([bytes[0], bytes[1], bytes[2]])
+    }
+}
+
+impl RawLayout for Rgba8 {
+    type Pixel = Rgba<u8>;
+    const BYTES_PER_PIXEL: usize = 4;
+    fn decode(bytes: &[u8]) -> Rgba<u8> {
+        Rgba([bytes[0], bytes[1], bytes[2], bytes[3]])
+    }
+}
+
+impl RawLayout for Gray8 {
+    type Pixel = Luma<u8>;
+    const BYTES_PER_PIXEL: usize = 1;
+    fn decode(bytes: &[u8]) -> Luma<u8> {
+        Luma([bytes[0]])
+    }
+}
+
+/// A borrowed, strided view over a raw frame buffer.
+struct RawView<'a, L> {
+    buf: &'a [u8],
+    width: u32,
+    height: u32,
+    stride: usize,
+    layout: std::marker::PhantomData<L>,
+}
+
+impl<L: RawLayout> GenericImageView for RawView<'_, L> {
+    type Pixel = L::Pixel;
+
+    fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn get_pixel(&self, x: u32, y: u32) -> L::Pixel {
+        let offset = y as usize * self.stride + x as usize * L::BYTES_PER_PIXEL;
+        L::decode(&self.buf[offset..offset + L::BYTES_PER_PIXEL])
+    }
+}
+
+impl Grid {
+    /// Creates a grid from a raw, strided frame buffer without copying it into an image.
+    ///
+    /// Detection is the same as [`Grid::try_from_image_with_config`]; the buffer
+    /// is only read while binarizing. Like other views, raw buffers are always
+    /// detected at full resolution, ignoring [`GridConfig::pyramid`].
+    ///
+    /// # Arguments
+    /// * `buf` - The pixel data, row after row.
+    /// * `width` - The width of the frame in pixels.
+    /// * `height` - The height of the frame in pixels.
+    /// * `stride` - The number of bytes between the starts of two consecutive rows.
+    /// * `format` - The layout of each pixel.
+    /// * `config` - The grid configuration.
+    ///
+    /// # Errors
+    /// [`GridError::InvalidDimensions`] for an empty frame, [`GridError::InvalidStride`]
+    /// if a row does not fit in `stride` bytes and [`GridError::BufferTooSmall`] if
+    /// `buf` does not hold every row, with a `required` length of `usize::MAX` when
+    /// the frame is larger than memory can address.
+    ///
+    /// # Example
+    /// ```
+    /// use grider::{Grid, GridConfig, PixelFormat};
+    ///
+    /// // A 4x2 BGRA frame padded to 20 bytes per row: a black row above a white one
+    /// let mut frame = vec![0u8; 40];
+    /// frame[20..36].fill(255);
+    /// let config = GridConfig {
+    ///     binarizer: grider::Binarizer::Global(128),
+    ///     merge_threshold_ratio: 0.0,
+    ///     ..Default::default()
+    /// };
+    ///
+    /// let grid = Grid::from_raw(&frame, 4, 2, 20, PixelFormat::Bgra8, config).unwrap();
+    /// assert_eq!(grid.rows.len(), 2);
+    /// ```
+    pub fn from_raw(
+        buf: &[u8],
+        width: u32,
+        height: u32,
+        stride: usize,
+        format: PixelFormat,
+        config: GridConfig,
+    ) -> Result<Self, GridError> {
+        debug!(
+            "Processing raw {:?} buffer with width={}, height={}, stride={}",
+            format, width, height, stride
+        );
+        if width == 0 || height == 0 {
+            return Err(GridError::InvalidDimensions { width, height });
+        }
+        let Some(row_length) = (width as usize).checked_mul(format.bytes_per_pixel()) else {
+            return Err(GridError::BufferTooSmall {
+                length: buf.len(),
+                required: usize::MAX,
+            });
+        };
+        if stride < row_length {
+            return Err(GridError::InvalidStride {
+                stride,
+                minimum: row_length,
+            });
+        }
+        // The last row does not need to be padded
+        let required = stride
+            .checked_mul(height as usize - 1)
+            .and_then(|padded| padded.checked_add(row_length))
+            .unwrap_or(usize::MAX);
+        if buf.len() < required {
+            return Err(GridError::BufferTooSmall {
+                length: buf.len(),
+                required,
+            });
+        }
+
+        fn detect<L: RawLayout>(
+            buf: &[u8],
+            width: u32,
+            height: u32,
+            stride: usize,
+            config: GridConfig,
+        ) -> Result<Grid, GridError> {
+            let view = RawView::<L> {
+                buf,
+                width,
+                height,
+                stride,
+                layout: std::marker::PhantomData,
+            };
+            Grid::try_from_view_with_config(&view, config)
+        }
+
+        match format {
+            PixelFormat::Bgra8 => detect::<Bgra8>(buf, width, height, stride, config),
+            PixelFormat::Rgb8 => detect::<Rgb8>(buf, width, height, stride, config),
+            PixelFormat::Rgba8 => detect::<Rgba8>(buf, width, height, stride, config),
+            PixelFormat::Gray8 => detect::<Gray8>(buf, width, height, stride, config),
+        }
+    }
+}
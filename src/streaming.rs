@@ -0,0 +1,245 @@
+use crate::binarize;
+use crate::{Column, Grid, GridConfig, GridDetector, GridError, LineInfo, LineTrait, Row};
+use image::{ColorType, DynamicImage, GrayImage};
+use tracing::*;
+
+/// Incremental grid detection for images received a few rows at a time, such
+/// as continuously scrolling captures.
+///
+/// Rows are binarized as soon as enough rows below them have been received for
+/// the binarizer, then classified. Runs of rows are closed as soon as a row of
+/// another kind follows them, and column ink is accumulated as rows arrive, so
+/// neither the image nor its per-row ink is kept in memory.
+///
+/// Closed runs are raw, as collected before the hysteresis, minimum run lengths
+/// and merging of [`GridConfig`], which depend on every run of the image. They
+/// are applied by [`GridStream::finish`], whose grid is the same as
+/// [`Grid::try_from_image_with_config`] on the whole image.
+///
+/// Statistics of the whole image cannot be streamed:
+/// [`crate::Binarizer::Otsu`], background detection and
+/// [`GridConfig::separators`] are rejected, unless the emptiness signal is a
+/// color key or an alpha channel.
+///
+/// # Example
+/// ```
+/// use grider::{Grid, GridConfig, GridStream};
+/// use image::open;
+///
+/// let img = open("tests/large.png").unwrap();
+/// let config = GridConfig::default();
+/// let mut stream = GridStream::new(img.width(), config.clone()).unwrap();
+///
+/// // Feed the capture 50 rows at a time
+/// for y in (0..img.height()).step_by(50) {
+///     let rows = img.crop_imm(0, y, img.width(), 50.min(img.height() - y));
+///     for run in stream.push_rows(&rows).unwrap() {
+///         println!("Closed run at y={} of height {}", run.start, run.length);
+///     }
+/// }
+/// let grid = stream.finish().unwrap();
+/// assert_eq!(grid, Grid::try_from_image_with_config(&img, config).unwrap());
+/// ```
+#[derive(Debug)]
+pub struct GridStream {
+    width: u32,
+    detector: GridDetector,
+    /// Color type of the rows, settled by the first rows pushed
+    color: Option<ColorType>,
+    /// Rows kept around the rows to binarize next, for local binarizers
+    overlap: u32,
+    /// Grayscale rows from `window_start` to `received`, for luma thresholding
+    window: Vec<u8>,
+    window_start: u32,
+    /// Number of rows received
+    received: u32,
+    /// Number of rows binarized and classified
+    classified: u32,
+    /// Run of rows still open, ended by a row of another kind
+    open_run: Option<LineInfo>,
+    runs: Vec<LineInfo>,
+    columns: Vec<u32>,
+    gray: GrayImage,
+}
+
+impl GridStream {
+    /// Creates a stream of rows of the given width.
+    ///
+    /// # Errors
+    /// [`GridError::InvalidDimensions`] for a width of 0.
+    pub fn new(width: u32, config: GridConfig) -> Result<Self, GridError> {
+        trace!(
+            "Streaming rows of width {} with config: {:?}",
+            width,
+            config
+        );
+        if width == 0 {
+            error!("Invalid image dimensions: width={}, height=0", width);
+            return Err(GridError::InvalidDimensions { width, height: 0 });
+        }
+        Ok(GridStream {
+            width,
+            detector: GridDetector::new(config),
+            color: None,
+            overlap: 0,
+            window: Vec::new(),
+            window_start: 0,
+            received: 0,
+            classified: 0,
+            open_run: None,
+            runs: Vec::new(),
+            columns: vec![0; width as usize],
+            gray: GrayImage::default(),
+        })
+    }
+
+    /// Returns the configuration of the stream.
+    pub fn config(&self) -> &GridConfig {
+        self.detector.config()
+    }
+
+    /// Returns the number of rows received so far.
+    pub fn height(&self) -> u32 {
+        self.received
+    }
+
+    /// Returns the runs of rows closed so far, from top to bottom.
+    pub fn closed_rows(&self) -> &[LineInfo] {
+        &self.runs
+    }
+
+    /// Appends rows to the bottom of the image.
+    ///
+    /// # Returns
+    /// The runs of rows closed by these rows, which may be none while a run
+    /// continues or while rows wait for the rows below them to be binarized.
+    ///
+    /// # Errors
+    /// [`GridError::InvalidBand`] if the rows are not as wide as the stream or
+    /// of another color type than the first rows, and
+    /// [`GridError::UnsupportedConfig`] if the configuration needs statistics of
+    /// the whole image.
+    pub fn push_rows(&mut self, rows: &DynamicImage) -> Result<&[LineInfo], GridError> {
+        let _span = debug_span!("push_rows").entered();
+        let closed = self.runs.len();
+        if rows.height() == 0 {
+            return Ok(&[]);
+        }
+        let color = *self.color.get_or_insert(rows.color());
+        if rows.width() != self.width || rows.color() != color {
+            error!(
+                "Invalid rows at y={}: {}x{} {:?}",
+                self.received,
+                rows.width(),
+                rows.height(),
+                rows.color()
+            );
+            return Err(GridError::InvalidBand {
+                start: self.received,
+                width: rows.width(),
+                height: rows.height(),
+            });
+        }
+        if self.received == 0 {
+            self.overlap = self.detector.local_overlap(color)?;
+        }
+
+        if self.detector.uses_luma(color) {
+            // Keep grayscale rows until the rows below them are received
+            match rows {
+                DynamicImage::ImageLuma8(img) => self.window.extend_from_slice(img.as_raw()),
+                other => {
+                    binarize::luma_into(other, &mut self.gray);
+                    self.window.extend_from_slice(self.gray.as_raw());
+                }
+            }
+            self.received += rows.height();
+            self.classify_window(self.received.saturating_sub(self.overlap))?;
+        } else {
+            // Masks depend on each pixel alone
+            let y = self.received;
+            self.detector.binarize_at(rows, (0, y))?;
+            self.received += rows.height();
+            self.classify_binarized(y, 0, rows.height());
+        }
+        Ok(&self.runs[closed..])
+    }
+
+    /// Classifies the rows still waiting for the rows below them, then segments
+    /// the rows and columns of the whole image.
+    ///
+    /// # Errors
+    /// [`GridError::InvalidDimensions`] if no rows were pushed.
+    pub fn finish(mut self) -> Result<Grid, GridError> {
+        let _span = debug_span!("finish_stream").entered();
+        if self.received == 0 {
+            error!("Invalid image dimensions: width={}, height=0", self.width);
+            return Err(GridError::InvalidDimensions {
+                width: self.width,
+                height: 0,
+            });
+        }
+        self.classify_window(self.received)?;
+        self.runs.extend(self.open_run.take());
+
+        let config = self.detector.config();
+        let rows = debug_span!("row_pass").in_scope(|| Grid::segment_runs(self.runs, config));
+        let columns = debug_span!("column_pass")
+            .in_scope(|| Grid::segment_profile(&self.columns, self.received, config));
+        Ok(Grid {
+            rows: rows.into_iter().map(Row::new).collect(),
+            columns: columns.into_iter().map(Column::new).collect(),
+        })
+    }
+
+    /// Binarizes the grayscale window and classifies its rows up to `end`, then
+    /// drops the rows no longer needed around the next ones.
+    fn classify_window(&mut self, end: u32) -> Result<(), GridError> {
+        if end <= self.classified || self.window.is_empty() {
+            return Ok(());
+        }
+        let height = self.received - self.window_start;
+        let window = GrayImage::from_raw(self.width, height, std::mem::take(&mut self.window))
+            .expect("window matches dimensions");
+        let window = DynamicImage::ImageLuma8(window);
+        let binarized = self.detector.binarize_at(&window, (0, self.window_start));
+        self.window = window.into_luma8().into_raw();
+        binarized?;
+
+        let (y, offset) = (self.classified, self.classified - self.window_start);
+        self.classify_binarized(y, offset, end - y);
+
+        let keep_from = self.classified.saturating_sub(self.overlap);
+        self.window
+            .drain(..(keep_from - self.window_start) as usize * self.width as usize);
+        self.window_start = keep_from;
+        Ok(())
+    }
+
+    /// Classifies `count` binarized rows of the detector, from row `offset` of
+    /// the binarized buffer, as the rows of the image starting at `y`.
+    fn classify_binarized(&mut self, y: u32, offset: u32, count: u32) {
+        let width = self.width as usize;
+        let pixels = &self.detector.binarized().as_raw()
+            [offset as usize * width..(offset + count) as usize * width];
+        let config = self.detector.config();
+
+        for (y, pixels) in (y..).zip(pixels.chunks_exact(width)) {
+            let mut ink = 0;
+            for (column_ink, &pixel) in self.columns.iter_mut().zip(pixels) {
+                let is_ink = (pixel != 255) as u32;
+                *column_ink += is_ink;
+                ink += is_ink;
+            }
+            let kind = Grid::classify_ink_count(ink, self.width, config);
+            match &mut self.open_run {
+                Some(run) if run.kind == kind => run.length += 1,
+                open_run => {
+                    self.runs.extend(open_run.take());
+                    *open_run = Some(LineInfo::new(y, 1, kind));
+                }
+            }
+        }
+        self.classified = y + count;
+    }
+}
@@ -0,0 +1,320 @@
+use crate::binarize::{self, Histogram};
+use crate::profile::ProjectionProfiles;
+use crate::{BackgroundDetection, Binarizer, Grid, GridConfig, GridDetector, GridError};
+use image::{ColorType, DynamicImage, GrayImage};
+use std::ops::Range;
+use tracing::*;
+
+/// Bytes of each integral image entry: a sum and a squared sum.
+const INTEGRAL_BYTES: usize = 2 * size_of::<u64>();
+
+/// Reads an image band by band through a callback, checking every band.
+struct BandReader<F> {
+    width: u32,
+    height: u32,
+    color: ColorType,
+    band_rows: u32,
+    read_band: F,
+}
+
+impl<F: FnMut(Range<u32>) -> Result<DynamicImage, GridError>> BandReader<F> {
+    /// Returns the ranges of rows of consecutive bands, from top to bottom.
+    fn bands(&self) -> impl Iterator<Item = Range<u32>> {
+        let (height, band_rows) = (self.height, self.band_rows);
+        (0..height)
+            .step_by(band_rows as usize)
+            .map(move |start| start..height.min(start + band_rows))
+    }
+
+    /// Reads a band of rows, rejecting bands of another size or color type.
+    fn read(&mut self, rows: Range<u32>) -> Result<DynamicImage, GridError> {
+        trace!("Reading band of rows {:?}", rows);
+        let band = (self.read_band)(rows.clone())?;
+        if band.width() != self.width
+            || band.height() != rows.len() as u32
+            || band.color() != self.color
+        {
+            error!("Invalid band for rows {:?}: {:?}", rows, band.color());
+            return Err(GridError::InvalidBand {
+                start: rows.start,
+                width: band.width(),
+                height: band.height(),
+            });
+        }
+        Ok(band)
+    }
+}
+
+impl Grid {
+    /// Creates a grid from an image read in horizontal bands, without holding the
+    /// whole image or its binarized copy in memory.
+    ///
+    /// `read_band` is called with ranges of rows and returns them as an image of
+    /// the full width and of type `color`, e.g. from a streaming decoder. Bands
+    /// are binarized one at a time, with enough rows around them for local
+    /// binarizers, and their ink is accumulated into the row and column profiles.
+    /// The result is the same as [`Grid::try_from_image_with_config`] on the whole
+    /// image.
+    ///
+    /// Bands are requested from top to bottom, in as many passes as needed:
+    /// a first pass when [`Binarizer::Otsu`] or background detection need
+    /// statistics of the whole image, and a last pass to erase the
+    /// [`GridConfig::separators`] when some are found.
+    ///
+    /// # Arguments
+    /// * `width` - The width of the image in pixels.
+    /// * `height` - The height of the image in pixels.
+    /// * `color` - The color type of the bands.
+    /// * `memory_limit` - Maximum number of bytes held at once by the bands, their
+    ///   grayscale and binarized copies, the binarizer tables and the profiles.
+    /// * `config` - The grid configuration.
+    /// * `read_band` - Returns the rows of a range as an image.
+    ///
+    /// # Errors
+    /// [`GridError::InvalidDimensions`] for an empty image,
+    /// [`GridError::MemoryLimitTooSmall`] if a single band does not fit in the
+    /// limit and [`GridError::InvalidBand`] if `read_band` returns a band of the
+    /// wrong size or color type. Errors of `read_band` are returned as is.
+    ///
+    /// # Example
+    /// ```
+    /// use grider::{Grid, GridConfig};
+    /// use image::{open, ColorType};
+    ///
+    /// let img = open("tests/large.png").unwrap();
+    /// let config = GridConfig::default();
+    ///
+    /// // Bands are cropped from an image in memory here; a decoder would produce them
+    /// let grid = Grid::from_bands(
+    ///     img.width(),
+    ///     img.height(),
+    ///     img.color(),
+    ///     1 << 20,
+    ///     config.clone(),
+    ///     |rows| Ok(img.crop_imm(0, rows.start, img.width(), rows.len() as u32)),
+    /// )
+    /// .unwrap();
+    /// assert_eq!(grid, Grid::try_from_image_with_config(&img, config).unwrap());
+    /// ```
+    pub fn from_bands(
+        width: u32,
+        height: u32,
+        color: ColorType,
+        memory_limit: usize,
+        config: GridConfig,
+        read_band: impl FnMut(Range<u32>) -> Result<DynamicImage, GridError>,
+    ) -> Result<Self, GridError> {
+        let _span = debug_span!("detect_grid_tiled").entered();
+        trace!("Processing bands with config: {:?}", config);
+        if width == 0 || height == 0 {
+            error!(
+                "Invalid image dimensions: width={}, height={}",
+                width, height
+            );
+            return Err(GridError::InvalidDimensions { width, height });
+        }
+
+        // Local binarizers need the rows around each band
+        let uses_luma =
+            config.color_key.is_none() && !(config.alpha_cutoff.is_some() && color.has_alpha());
+        let is_local = !matches!(config.binarizer, Binarizer::Global(_) | Binarizer::Otsu);
+        let overlap = if uses_luma && is_local {
+            config.threshold_block_size
+        } else {
+            0
+        };
+        let band_rows = Self::band_rows(width, height, color, overlap, memory_limit)?;
+        debug!(
+            "Reading bands of {} rows with an overlap of {} rows",
+            band_rows, overlap
+        );
+        let mut reader = BandReader {
+            width,
+            height,
+            color,
+            band_rows,
+            read_band,
+        };
+
+        // Statistics of the whole image are settled before binarizing any band
+        let mut band_config = config.clone();
+        if uses_luma
+            && (config.background != BackgroundDetection::None
+                || config.binarizer == Binarizer::Otsu)
+        {
+            Self::settle_global_statistics(&mut reader, &mut band_config)?;
+        }
+        let mut detector = GridDetector::new(band_config);
+
+        // Binarize each band with its overlap and count the ink of its own rows
+        let mut profiles = ProjectionProfiles {
+            rows: vec![0; height as usize],
+            columns: vec![0; width as usize],
+        };
+        Self::for_each_binarized_band(&mut reader, &mut detector, overlap, |y, pixels| {
+            profiles.add_rows(y, pixels)
+        })?;
+
+        // Count the ink again without the separators found in the profiles
+        let (row_separators, column_separators) = match &config.separators {
+            Some(separators) => {
+                let _span = debug_span!("separators").entered();
+                let (row_separators, column_separators) =
+                    Self::find_profile_separators(&profiles, separators);
+                if row_separators.contains(&true) || column_separators.contains(&true) {
+                    profiles.columns.fill(0);
+                    Self::for_each_binarized_band(
+                        &mut reader,
+                        &mut detector,
+                        overlap,
+                        |y, pixels| {
+                            add_rows_without_separators(
+                                &mut profiles,
+                                y,
+                                pixels,
+                                &row_separators,
+                                &column_separators,
+                            )
+                        },
+                    )?;
+                }
+                (row_separators, column_separators)
+            }
+            None => (Vec::new(), Vec::new()),
+        };
+
+        Self::from_profiles(&profiles, &config, &row_separators, &column_separators)
+    }
+
+    /// Returns the largest number of rows per band that keeps the buffers within
+    /// the memory limit.
+    fn band_rows(
+        width: u32,
+        height: u32,
+        color: ColorType,
+        overlap: u32,
+        memory_limit: usize,
+    ) -> Result<u32, GridError> {
+        let (width, height) = (width as usize, height as usize);
+        // Profiles and separator flags of every line
+        let fixed = (width + height) * (size_of::<u32>() + size_of::<bool>())
+            + (width + 1) * INTEGRAL_BYTES;
+        // Band, grayscale and binarized pixels and binarizer tables of a row
+        let per_row = width * (color.bytes_per_pixel() as usize + 2) + (width + 1) * INTEGRAL_BYTES;
+        let required = |rows: usize| fixed.saturating_add(rows.saturating_mul(per_row));
+
+        let max_rows = memory_limit.saturating_sub(fixed) / per_row;
+        let band_rows = if max_rows >= height {
+            height
+        } else {
+            max_rows.saturating_sub(2 * overlap as usize)
+        };
+        if band_rows == 0 {
+            let required = required(height.min(1 + 2 * overlap as usize));
+            error!(
+                "Memory limit too small: limit={}, required={}",
+                memory_limit, required
+            );
+            return Err(GridError::MemoryLimitTooSmall {
+                limit: memory_limit,
+                required,
+            });
+        }
+        Ok(band_rows as u32)
+    }
+
+    /// Reads every band once to settle the background and Otsu level of the
+    /// whole image, so that bands are binarized with a fixed inversion and level.
+    fn settle_global_statistics<F: FnMut(Range<u32>) -> Result<DynamicImage, GridError>>(
+        reader: &mut BandReader<F>,
+        config: &mut GridConfig,
+    ) -> Result<(), GridError> {
+        let _span = debug_span!("global_statistics").entered();
+        let mut background: Histogram = [0; 256];
+        let mut levels: Histogram = [0; 256];
+        let mut gray = GrayImage::default();
+
+        for rows in reader.bands().collect::<Vec<_>>() {
+            let band = reader.read(rows.clone())?;
+            let band = match &band {
+                DynamicImage::ImageLuma8(img) => img,
+                other => {
+                    binarize::luma_into(other, &mut gray);
+                    &gray
+                }
+            };
+            config
+                .background
+                .count(band, rows.start, reader.height, &mut background);
+            if config.binarizer == Binarizer::Otsu {
+                binarize::add_levels(band, &mut levels);
+            }
+        }
+
+        config.invert = config.invert != config.background.is_dark_level(&background);
+        config.background = BackgroundDetection::None;
+        if config.binarizer == Binarizer::Otsu {
+            if config.invert {
+                levels.reverse();
+            }
+            config.binarizer = Binarizer::Global(binarize::otsu_level(&levels));
+        }
+        debug!(
+            "Settled invert={} and binarizer={:?}",
+            config.invert, config.binarizer
+        );
+        Ok(())
+    }
+
+    /// Binarizes every band with the rows around it and passes the binarized
+    /// pixels of its own rows to `f`, along with the index of its first row.
+    fn for_each_binarized_band<F: FnMut(Range<u32>) -> Result<DynamicImage, GridError>>(
+        reader: &mut BandReader<F>,
+        detector: &mut GridDetector,
+        overlap: u32,
+        mut f: impl FnMut(u32, &[u8]),
+    ) -> Result<(), GridError> {
+        let _span = debug_span!("ink_profiles").entered();
+        let width = reader.width as usize;
+        for rows in reader.bands().collect::<Vec<_>>() {
+            let read = rows.start.saturating_sub(overlap)..reader.height.min(rows.end + overlap);
+            let band = reader.read(read.clone())?;
+            detector.binarize_at(&band, (0, read.start))?;
+
+            let offset = (rows.start - read.start) as usize * width;
+            let pixels = &detector.binarized().as_raw()[offset..offset + rows.len() * width];
+            f(rows.start, pixels);
+        }
+        Ok(())
+    }
+}
+
+/// Counts the ink of binarized rows starting at row `y` as if the separator
+/// pixels were erased.
+fn add_rows_without_separators(
+    profiles: &mut ProjectionProfiles,
+    y: u32,
+    pixels: &[u8],
+    row_separators: &[bool],
+    column_separators: &[bool],
+) {
+    let width = profiles.columns.len();
+    for (row, pixels) in (y as usize..).zip(pixels.chunks_exact(width)) {
+        if row_separators[row] {
+            profiles.rows[row] = 0;
+            continue;
+        }
+        let mut ink = 0;
+        for ((column_ink, &pixel), &separator) in profiles
+            .columns
+            .iter_mut()
+            .zip(pixels)
+            .zip(column_separators)
+        {
+            let is_ink = (pixel != 255 && !separator) as u32;
+            *column_ink += is_ink;
+            ink += is_ink;
+        }
+        profiles.rows[row] = ink;
+    }
+}